@@ -0,0 +1,2208 @@
+use std::{
+    cell::{
+        Cell,
+        RefCell,
+    },
+    collections::HashSet,
+    hash::{
+        DefaultHasher,
+        Hash,
+        Hasher,
+    },
+    num::NonZeroU32,
+    ops::Range,
+    rc::Rc,
+};
+
+use anyhow::Context;
+use ratatui::prelude::*;
+use ratatui_wgpu::Dimensions;
+use unicode_segmentation::UnicodeSegmentation;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    Element,
+    HtmlInputElement,
+    HtmlTextAreaElement,
+    SelectionMode,
+    Storage,
+};
+use winit::{
+    application::ApplicationHandler,
+    dpi::{
+        PhysicalPosition,
+        PhysicalSize,
+    },
+    event::{
+        ElementState,
+        MouseButton,
+        MouseScrollDelta,
+        WindowEvent,
+    },
+    event_loop::EventLoopProxy,
+    platform::web::*,
+    window::{
+        Window,
+        WindowAttributes,
+    },
+};
+
+use crate::{
+    config::{
+        Config,
+        DARK_COLOR_SCHEME_QUERY,
+        Theme,
+        prefers_dark_color_scheme,
+    },
+    edit::{
+        Insertion,
+        LineEnding,
+        auto_close,
+        base64_decode,
+        base64_encode,
+        duplicate_lines,
+        inserted,
+        line_span,
+        move_lines,
+        newline,
+        reformat_json,
+        sort_lines,
+        title_case,
+        toggle_comment,
+        unique_lines,
+        url_decode,
+        url_encode,
+    },
+    find::{
+        FindBar,
+        replace_ranges,
+    },
+    fonts::{
+        DEFAULT_FONT_SIZE_PX,
+        FONT_SIZE_STEP_PX,
+        FontSet,
+        LoadedFont,
+        fetch_font,
+        preload_fonts,
+    },
+    gpu::{
+        EditorBackend,
+        Effect,
+        Effects,
+        REDUCED_MOTION_QUERY,
+        build_terminal,
+        select_backends,
+    },
+    history::{
+        History,
+        Snapshot,
+    },
+    input::{
+        Action,
+        CONFIRM_BINDINGS,
+        FIND_BINDINGS,
+        GO_TO_LINE_BINDINGS,
+        KEY_BINDINGS,
+        MULTI_CLICK_MS,
+        Motion,
+        PALETTE_BINDINGS,
+        REPLACE_BINDINGS,
+        attach_editor,
+        attach_live_region,
+        attach_prompt_input,
+        byte_at,
+        install_drop_handler,
+        install_interaction_handler,
+        install_visibility_handler,
+        select,
+        selected_text,
+        selection,
+        watch_media_query,
+        word_at,
+    },
+    js_error,
+    prompt::{
+        Confirm,
+        GoToLine,
+        Help,
+        Palette,
+        line_start,
+    },
+    render::{
+        Caret,
+        FRAME_STATS_REFRESH_MS,
+        FrameStats,
+        RenderCache,
+        Screen,
+        ViewState,
+        minimap_lines_per_row,
+        offset_in_row,
+        row_end,
+        selection_range,
+    },
+    shader::{
+        ADJUSTMENT_STEP,
+        Adjustment,
+    },
+    storage::{
+        BRIGHTNESS_STORAGE_KEY,
+        CONTRAST_STORAGE_KEY,
+        DEFAULT_FILE_NAME,
+        DEMO_PAUSE_MS,
+        FileHandle,
+        HIGH_CONTRAST_STORAGE_KEY,
+        SAMPLE_TEXT,
+        STORAGE_KEY,
+        SaveState,
+        WRAP_STORAGE_KEY,
+        copy_to_clipboard,
+        download_text,
+        file_pickers_available,
+        local_storage,
+        open_file,
+        save_to_storage,
+        store_flag,
+        store_number,
+        write_file,
+    },
+    syntax::Language,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AppEvent {
+    Action(Action),
+    ReducedMotion(bool),
+    DarkColorScheme(bool),
+    FileLoaded {
+        name: String,
+        contents: String,
+        /// Where the file can be written back to, if it was opened through the
+        /// File System Access API.
+        handle: Option<FileHandle>,
+    },
+    /// The document was written to `handle` as `saved`, before its line
+    /// ending was applied.
+    FileSaved {
+        handle: FileHandle,
+        saved: String,
+        /// Whether auto-save wrote it, which isn't worth a message.
+        auto: bool,
+    },
+    Edited,
+    /// The user pressed a key, clicked or scrolled while the demo was running.
+    Interacted,
+    /// The text being composed through an IME so far.
+    Composing(String),
+    CompositionEnded,
+    Message(String),
+    VisibilityChanged {
+        hidden: bool,
+    },
+    FontLoaded {
+        name: &'static str,
+        data: &'static [u8],
+    },
+    FindQuery(String),
+    Replacement(String),
+    PaletteFilter(String),
+    GoToLineInput(String),
+    /// What was typed while a confirmation is asked for.
+    ConfirmInput(String),
+    DeviceLost(String),
+    Paste(String),
+    /// A bracket or quote typed into the editor, which the browser was kept
+    /// from inserting.
+    TypedPair(char),
+    /// Sent once the editor is attached and holds its initial document, with
+    /// the line ending that document had.
+    Ready {
+        line_ending: LineEnding,
+    },
+}
+
+pub struct App {
+    config: Config,
+    view: ViewState,
+    cache: RenderCache,
+    force_redraw: bool,
+    pending_resize: Option<PhysicalSize<u32>>,
+    zero_sized: bool,
+    hidden: bool,
+    cursor: PhysicalPosition<f64>,
+    /// Wheel movement that hasn't yet added up to a whole row or column.
+    wheel_remainder: (f64, f64),
+    drag_anchor: Option<u32>,
+    /// The pane whose scrollbar is being dragged.
+    scrollbar_drag: Option<usize>,
+    /// The IME composition in progress, if any.
+    composition: Option<String>,
+    last_click: Option<(f64, u32)>,
+    click_count: u8,
+    find: FindBar,
+    palette: Palette,
+    go_to_line: GoToLine,
+    confirm: Confirm,
+    help: Help,
+    stats: FrameStats,
+    history: History,
+    /// The name of the file the document was loaded from or last saved as.
+    file_name: Option<String>,
+    /// Where saving writes to, once the document has been opened from or
+    /// saved to a file picked through the File System Access API.
+    file_handle: Option<FileHandle>,
+    /// The text as it was last loaded or saved, which unsaved changes are
+    /// shown against.
+    saved: String,
+    /// Whether the text differs from what was last loaded or saved.
+    dirty: bool,
+    /// When `redraw` last ran, for holding to `Config::max_fps`.
+    last_render: f64,
+    /// When a large paste began and its size in bytes, until the frame showing
+    /// it is drawn.
+    paste_started: Option<(f64, usize)>,
+    /// Whether a frame has been drawn yet, until which the page's loading
+    /// indicator stays up.
+    drawn: bool,
+    storage: Option<Storage>,
+    /// When to auto-save the latest edits.
+    pending_save: Option<f64>,
+    demo: Option<Demo>,
+    save_state: SaveState,
+    proxy: EventLoopProxy<AppEvent>,
+    window: Rc<RefCell<Option<Window>>>,
+    fonts: Rc<RefCell<Option<FontSet>>>,
+    backend: Rc<RefCell<Option<Terminal<EditorBackend>>>>,
+    /// The graphics APIs the terminal was built with, reused when it is
+    /// rebuilt.
+    backends: Rc<Cell<wgpu::Backends>>,
+    text_input: Rc<RefCell<Option<HtmlTextAreaElement>>>,
+    find_input: Rc<RefCell<Option<HtmlInputElement>>>,
+    replace_input: Rc<RefCell<Option<HtmlInputElement>>>,
+    palette_input: Rc<RefCell<Option<HtmlInputElement>>>,
+    go_to_line_input: Rc<RefCell<Option<HtmlInputElement>>>,
+    confirm_input: Rc<RefCell<Option<HtmlInputElement>>>,
+    /// Where messages are put for screen readers to announce.
+    live_region: Rc<RefCell<Option<Element>>>,
+    /// When the last message announced expires, which tells it apart from
+    /// later messages with the same text.
+    announced: Option<f64>,
+}
+
+/// Animation frames don't land exactly on schedule, so a frame this close to
+/// due is drawn instead of waiting a whole extra tick.
+const FRAME_SLACK_MS: f64 = 2.0;
+
+/// A high resolution timestamp in milliseconds. `Instant` isn't available on
+/// wasm32-unknown-unknown. Every timer reads this clock, which unlike
+/// `Date::now` doesn't jump when the system clock is adjusted.
+pub fn now_ms() -> f64 {
+    web_sys::window()
+        .and_then(|win| win.performance())
+        .map_or_else(js_sys::Date::now, |performance| performance.now())
+}
+
+/// The id of the page's loading indicator, which covers the canvas until the
+/// first frame is drawn.
+const LOADING_ID: &str = "loading";
+
+fn hide_loading() {
+    if let Some(loading) = web_sys::window()
+        .and_then(|win| win.document())
+        .and_then(|doc| doc.get_element_by_id(LOADING_ID))
+    {
+        loading.remove();
+    }
+}
+
+/// How much each step of the live CRT controls scales the effect by.
+const CRT_STEP: f32 = 1.25;
+
+/// How much of the sample the demo has typed, in bytes, and when it types the
+/// next grapheme.
+struct Demo {
+    typed: usize,
+    next_at: f64,
+}
+
+/// Shows `message` over the editor, returning the overlay so it can be removed
+/// once it no longer applies.
+fn show_overlay(container_id: &str, message: &str) -> Option<Element> {
+    let doc = web_sys::window().and_then(|win| win.document())?;
+    let container = doc
+        .get_element_by_id(container_id)
+        .or_else(|| doc.body().map(Element::from))?;
+    let overlay = doc.create_element("pre").ok()?;
+
+    overlay.set_text_content(Some(message));
+    let _ = overlay.set_attribute(
+        "style",
+        "position: absolute; top: 50%; left: 50%; transform: translate(-50%, -50%); \
+         max-width: 80%; margin: 0; padding: 1em; white-space: pre-wrap; \
+         font-family: monospace; color: #eee; background: #222; border-radius: 0.5em; \
+         z-index: 2",
+    );
+    container.append_child(&overlay).ok()?;
+    Some(overlay)
+}
+
+/// Puts the editor's container in fullscreen, or leaves fullscreen if it is
+/// already. Either way the canvas resizes, which arrives as a normal resize.
+fn toggle_fullscreen(container_id: &str) {
+    let Some(doc) = web_sys::window().and_then(|win| win.document()) else {
+        return;
+    };
+    if doc.fullscreen_element().is_some() {
+        doc.exit_fullscreen();
+        return;
+    }
+    let Some(container) = doc.get_element_by_id(container_id) else {
+        return;
+    };
+
+    // Called through JS so the promise, which rejects when the page isn't
+    // allowed to go fullscreen, can be awaited.
+    let request = js_sys::Reflect::get(&container, &"requestFullscreen".into())
+        .ok()
+        .and_then(|request| request.dyn_into::<js_sys::Function>().ok())
+        .and_then(|request| request.call0(&container).ok())
+        .and_then(|promise| promise.dyn_into::<js_sys::Promise>().ok());
+    let Some(request) = request else {
+        log::warn!("Fullscreen isn't supported, staying windowed");
+        return;
+    };
+    wasm_bindgen_futures::spawn_local(async move {
+        if let Err(err) = JsFuture::from(request).await {
+            log::warn!("Fullscreen was refused, staying windowed: {err:?}");
+        }
+    });
+}
+
+impl ApplicationHandler<AppEvent> for App {
+    fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        self.window = Rc::new(RefCell::new(Some(
+            event_loop
+                .create_window(WindowAttributes::default().with_title(&self.config.title))
+                .unwrap(),
+        )));
+
+        let window = self.window.clone();
+        let font_set = self.fonts.clone();
+        let backend = self.backend.clone();
+        let chosen_backends = self.backends.clone();
+        let input = self.text_input.clone();
+        let find = self.find_input.clone();
+        let replace = self.replace_input.clone();
+        let palette = self.palette_input.clone();
+        let go_to_line = self.go_to_line_input.clone();
+        let confirm = self.confirm_input.clone();
+        let live_region = self.live_region.clone();
+        let proxy = self.proxy.clone();
+        let title = self.config.title.clone();
+        let canvas_id = self.config.canvas_id.clone();
+        let font_url = self.config.font_url.clone();
+        let fallback_urls = self.config.fallback_font_urls.clone();
+        let read_only = self.config.read_only;
+        let effect = self.config.effect;
+        let crt_settings = self.config.crt_settings;
+        let high_contrast = self.config.high_contrast && !self.config.high_contrast_effects;
+        let adjustment = self.config.adjustment;
+        let custom_shader = self.config.custom_shader.clone();
+        // A stored document is the user's own, which the demo would replace
+        // with the sample.
+        if self
+            .storage
+            .as_ref()
+            .is_some_and(|storage| matches!(storage.get_item(STORAGE_KEY), Ok(Some(_))))
+        {
+            self.config.demo = false;
+        }
+        let demo = self.config.demo;
+        wasm_bindgen_futures::spawn_local(async move {
+            let result = async {
+                let backends = select_backends().await.context(
+                    "This demo requires WebGPU or WebGL2, and neither is available in this browser.",
+                )?;
+                let webgl = backends == wgpu::Backends::GL;
+                chosen_backends.set(backends);
+
+                let document = web_sys::window()
+                    .and_then(|win| win.document())
+                    .context("No document to attach the editor to")?;
+                let dst = document.get_element_by_id(&canvas_id).with_context(|| {
+                    format!("No element with id `{canvas_id}` to attach the editor to")
+                })?;
+                let canvas = window
+                    .borrow()
+                    .as_ref()
+                    .and_then(|window| window.canvas())
+                    .context("The window has no canvas")?;
+
+                let reduced_motion = watch_media_query(
+                    REDUCED_MOTION_QUERY,
+                    proxy.clone(),
+                    AppEvent::ReducedMotion,
+                )
+                .unwrap_or_default();
+                watch_media_query(
+                    DARK_COLOR_SCHEME_QUERY,
+                    proxy.clone(),
+                    AppEvent::DarkColorScheme,
+                );
+
+                install_drop_handler(&canvas, proxy.clone())
+                    .context("Failed to listen for dropped files")?;
+                install_visibility_handler(&document, proxy.clone())
+                    .context("Failed to listen for visibility changes")?;
+                if demo {
+                    install_interaction_handler(&dst, proxy.clone())
+                        .context("Failed to listen for the end of the demo")?;
+                }
+
+                let initial_text = local_storage()
+                    .and_then(|storage| storage.get_item(STORAGE_KEY).ok().flatten())
+                    .unwrap_or_else(|| SAMPLE_TEXT.to_string());
+
+                let (text_input, height, width) =
+                    attach_editor(&document, &dst, &canvas, &initial_text, &title, proxy.clone())
+                        .with_context(|| {
+                            format!("Failed to attach the editor canvas to `{canvas_id}`")
+                        })?;
+                text_input.set_read_only(read_only);
+                let find_input = attach_prompt_input(
+                    &document,
+                    &dst,
+                    "Find",
+                    proxy.clone(),
+                    &[FIND_BINDINGS, KEY_BINDINGS],
+                    AppEvent::FindQuery,
+                )
+                .context("Failed to create the find bar")?;
+                let replace_input = attach_prompt_input(
+                    &document,
+                    &dst,
+                    "Replace with",
+                    proxy.clone(),
+                    &[REPLACE_BINDINGS, FIND_BINDINGS, KEY_BINDINGS],
+                    AppEvent::Replacement,
+                )
+                .context("Failed to create the find bar")?;
+                let go_to_line_input = attach_prompt_input(
+                    &document,
+                    &dst,
+                    "Go to line",
+                    proxy.clone(),
+                    &[GO_TO_LINE_BINDINGS, KEY_BINDINGS],
+                    AppEvent::GoToLineInput,
+                )
+                .context("Failed to create the go to line prompt")?;
+                let confirm_input = attach_prompt_input(
+                    &document,
+                    &dst,
+                    "Confirm, y or n",
+                    proxy.clone(),
+                    &[CONFIRM_BINDINGS, KEY_BINDINGS],
+                    AppEvent::ConfirmInput,
+                )
+                .context("Failed to create the confirmation prompt")?;
+                let palette_input = attach_prompt_input(
+                    &document,
+                    &dst,
+                    "Command palette",
+                    proxy.clone(),
+                    &[PALETTE_BINDINGS, KEY_BINDINGS],
+                    AppEvent::PaletteFilter,
+                )
+                .context("Failed to create the command palette")?;
+                *live_region.borrow_mut() = attach_live_region(&document, &dst);
+
+                window
+                    .borrow_mut()
+                    .as_mut()
+                    .unwrap()
+                    .set_prevent_default(false);
+
+                let font =
+                    LoadedFont::new("Noto Sans Mono", include_bytes!("fonts/NotoSansMono.ttf"))
+                        .context("The primary font failed to load")?;
+                let emoji = LoadedFont::new(
+                    "Noto Color Emoji",
+                    include_bytes!("fonts/NotoColorEmoji-Regular.ttf"),
+                )
+                .context("The emoji font failed to load")?;
+
+                let mut primary = vec![font];
+                let mut font_notice = None;
+                // The fetch has to finish before the backend is built so the requested
+                // font is the one it starts with.
+                if let Some(url) = &font_url {
+                    match fetch_font("Custom font", url).await {
+                        Ok(font) => primary.insert(0, font),
+                        Err(err) => {
+                            log::warn!("Failed to load the font at {url}: {err:#}");
+                            font_notice =
+                                Some(format!("Couldn't load {url}, using the bundled font"));
+                        }
+                    }
+                }
+
+                let mut fallbacks = vec![emoji];
+                for url in &fallback_urls {
+                    match fetch_font("Fallback font", url).await {
+                        Ok(font) => fallbacks.push(font),
+                        Err(err) => {
+                            log::warn!("Failed to load the fallback font at {url}: {err:#}")
+                        }
+                    }
+                }
+
+                let mut fonts = FontSet {
+                    primary,
+                    current: 0,
+                    fallbacks,
+                    size_px: DEFAULT_FONT_SIZE_PX,
+                    reported: HashSet::new(),
+                };
+                fonts.report_missing(&initial_text);
+
+                let terminal = build_terminal(
+                    canvas,
+                    &fonts,
+                    backends,
+                    Dimensions { width, height },
+                    Effects {
+                        effect,
+                        crt_settings,
+                        reduced_motion,
+                        high_contrast,
+                        adjustment,
+                    },
+                    custom_shader,
+                    proxy.clone(),
+                )
+                .await?;
+                *backend.borrow_mut() = Some(terminal);
+                *font_set.borrow_mut() = Some(fonts);
+                // Events are dropped until the terminal exists, so this waits until now.
+                if let Some(notice) = font_notice {
+                    let _ = proxy.send_event(AppEvent::Message(notice));
+                }
+                if webgl {
+                    let _ = proxy.send_event(AppEvent::Message(
+                        "WebGPU is unavailable, rendering with WebGL2".to_string(),
+                    ));
+                }
+                preload_fonts(proxy.clone());
+
+                text_input.focus().map_err(js_error)?;
+                *input.borrow_mut() = Some(text_input);
+                *find.borrow_mut() = Some(find_input);
+                *replace.borrow_mut() = Some(replace_input);
+                *palette.borrow_mut() = Some(palette_input);
+                *go_to_line.borrow_mut() = Some(go_to_line_input);
+                *confirm.borrow_mut() = Some(confirm_input);
+                let _ = proxy.send_event(AppEvent::ReducedMotion(reduced_motion));
+                let _ = proxy.send_event(AppEvent::Ready {
+                    line_ending: LineEnding::detect(&initial_text),
+                });
+
+                anyhow::Ok(())
+            }
+            .await;
+
+            if let Err(err) = result {
+                log::error!("{err:#}");
+                hide_loading();
+                show_overlay(&canvas_id, &format!("{err:#}"));
+            }
+        });
+    }
+
+    fn user_event(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop, event: AppEvent) {
+        if let AppEvent::DeviceLost(message) = event {
+            self.reconnect(&message);
+            return;
+        }
+
+        let backend = self.backend.clone();
+        let mut terminal = backend.borrow_mut();
+        let Some(terminal) = terminal.as_mut() else {
+            return;
+        };
+
+        match event {
+            AppEvent::Action(Action::ToggleCrt) => {
+                // Kept in the config so a rebuilt terminal starts the same way.
+                self.config.effect = if self.config.effect == Effect::Crt {
+                    Effect::Plain
+                } else {
+                    Effect::Crt
+                };
+                terminal
+                    .backend_mut()
+                    .post_processor_mut()
+                    .set_effect(self.config.effect);
+            }
+            AppEvent::Action(Action::CycleEffect) => {
+                let post_processor = terminal.backend_mut().post_processor_mut();
+                self.config.effect = self.config.effect.next();
+                while !post_processor.available(self.config.effect) {
+                    self.config.effect = self.config.effect.next();
+                }
+                post_processor.set_effect(self.config.effect);
+                self.view
+                    .notify(format!("Effect: {}", self.config.effect.name()), now_ms());
+            }
+            AppEvent::Action(action @ (Action::StrengthenCrt | Action::WeakenCrt)) => {
+                let factor = if action == Action::StrengthenCrt {
+                    CRT_STEP
+                } else {
+                    1.0 / CRT_STEP
+                };
+                self.config.crt_settings = self.config.crt_settings.scaled(factor);
+                terminal
+                    .backend_mut()
+                    .post_processor_mut()
+                    .set_crt_settings(self.config.crt_settings);
+            }
+            AppEvent::Action(
+                action @ (Action::Brighten
+                | Action::Dim
+                | Action::IncreaseContrast
+                | Action::DecreaseContrast
+                | Action::ResetAdjustment),
+            ) => {
+                let adjustment = &mut self.config.adjustment;
+                match action {
+                    Action::Brighten => adjustment.brightness += ADJUSTMENT_STEP,
+                    Action::Dim => adjustment.brightness -= ADJUSTMENT_STEP,
+                    Action::IncreaseContrast => adjustment.contrast += ADJUSTMENT_STEP,
+                    Action::DecreaseContrast => adjustment.contrast -= ADJUSTMENT_STEP,
+                    _ => *adjustment = Adjustment::default(),
+                }
+                *adjustment = adjustment.clamped();
+                let adjustment = *adjustment;
+                terminal
+                    .backend_mut()
+                    .post_processor_mut()
+                    .set_adjustment(adjustment);
+                store_number(&self.storage, BRIGHTNESS_STORAGE_KEY, adjustment.brightness);
+                store_number(&self.storage, CONTRAST_STORAGE_KEY, adjustment.contrast);
+                self.view.notify(
+                    format!(
+                        "Brightness {:.0}%, contrast {:.0}%",
+                        adjustment.brightness * 100.0,
+                        adjustment.contrast * 100.0
+                    ),
+                    now_ms(),
+                );
+            }
+            AppEvent::Action(Action::Copy) => {
+                let text = selected_text(self.text_input.borrow().as_ref().unwrap());
+                if !text.is_empty() && !copy_to_clipboard(text, self.proxy.clone()) {
+                    self.view
+                        .notify("The clipboard isn't available on this page", now_ms());
+                }
+            }
+            AppEvent::Action(Action::Save) if file_pickers_available() => {
+                let value = self.text_input.borrow().as_ref().unwrap().value();
+                write_file(
+                    self.file_handle.clone(),
+                    self.file_name
+                        .as_deref()
+                        .unwrap_or(DEFAULT_FILE_NAME)
+                        .to_string(),
+                    self.config.line_ending.apply(&value).into_owned(),
+                    value,
+                    false,
+                    self.proxy.clone(),
+                );
+            }
+            AppEvent::Action(Action::Save) => {
+                let value = self.text_input.borrow().as_ref().unwrap().value();
+                let file_name = self.file_name.as_deref().unwrap_or(DEFAULT_FILE_NAME);
+                match download_text(file_name, &self.config.line_ending.apply(&value)) {
+                    Ok(()) => {
+                        self.view.notify(format!("Saved {file_name}"), now_ms());
+                        self.mark_saved(&value);
+                    }
+                    Err(err) => self
+                        .view
+                        .notify(format!("Failed to save: {err:?}"), now_ms()),
+                }
+            }
+            AppEvent::Action(Action::CycleFont) => {
+                let mut fonts = self.fonts.borrow_mut();
+                let fonts = fonts.as_mut().unwrap();
+                if fonts.primary.len() > 1 {
+                    fonts.cycle();
+                    terminal.backend_mut().update_fonts(fonts.fonts());
+                    self.view
+                        .notify(format!("Font: {}", fonts.current().name), now_ms());
+                } else {
+                    self.view.notify("No other fonts are available", now_ms());
+                }
+            }
+            AppEvent::Action(action @ (Action::ZoomIn | Action::ZoomOut | Action::ZoomReset)) => {
+                let mut fonts = self.fonts.borrow_mut();
+                let fonts = fonts.as_mut().unwrap();
+                let size_px = match action {
+                    Action::ZoomIn => fonts.size_px + FONT_SIZE_STEP_PX,
+                    Action::ZoomOut => fonts.size_px.saturating_sub(FONT_SIZE_STEP_PX),
+                    _ => DEFAULT_FONT_SIZE_PX,
+                };
+                if fonts.resize(size_px) {
+                    // The terminal picks up its new size in cells on the next draw, and hit
+                    // testing always measures cells from the current size.
+                    terminal.backend_mut().update_fonts(fonts.fonts());
+                }
+                self.view
+                    .notify(format!("Font size: {}px", fonts.size_px), now_ms());
+            }
+            AppEvent::Action(action @ (Action::Find | Action::OpenReplace)) => {
+                self.find.open = true;
+                self.find.replacing = action == Action::OpenReplace;
+                self.find.replace_focused = false;
+                self.find
+                    .update(&self.text_input.borrow().as_ref().unwrap().value());
+                self.find_input.borrow().as_ref().unwrap().select();
+                self.focus_input();
+            }
+            AppEvent::Action(Action::CloseFind) => {
+                self.find.open = false;
+                self.find.update("");
+                self.focus_input();
+            }
+            AppEvent::Action(Action::SwitchFindField) => {
+                if self.find.replacing {
+                    self.find.replace_focused = !self.find.replace_focused;
+                    self.focus_input();
+                }
+            }
+            AppEvent::Action(
+                Action::ReplaceNext
+                | Action::ReplaceAll
+                | Action::SortLines
+                | Action::UniqueLines
+                | Action::Uppercase
+                | Action::Lowercase
+                | Action::TitleCase
+                | Action::Base64Encode
+                | Action::Base64Decode
+                | Action::UrlEncode
+                | Action::UrlDecode
+                | Action::PrettifyJson
+                | Action::MinifyJson
+                | Action::ToggleComment
+                | Action::MoveLinesUp
+                | Action::MoveLinesDown
+                | Action::DuplicateLines
+                | Action::NewDocument
+                | Action::ResetToSample
+                | Action::OpenFile,
+            ) if self.config.read_only => {
+                self.view.notify("The document is read-only", now_ms());
+            }
+            AppEvent::Action(Action::ReplaceNext) => {
+                let input = self.text_input.borrow().clone().unwrap();
+                let current = selection(&input);
+                if self.find.matches.contains(&current) {
+                    let (text, caret) = replace_ranges(
+                        &input.value(),
+                        &[current.clone()],
+                        &self.find.replacement,
+                        current.end,
+                    );
+                    input.set_value(&text);
+                    select(&input, caret, caret);
+                    self.document_changed(false);
+                }
+
+                if let Some(found) = self.find.step(&selection(&input), true) {
+                    select(&input, found.start, found.end);
+                }
+            }
+            AppEvent::Action(Action::ReplaceAll) => {
+                let input = self.text_input.borrow().clone().unwrap();
+                let count = self.find.matches.len();
+                if count == 0 {
+                    self.view.notify("No matches", now_ms());
+                } else {
+                    let (text, caret) = replace_ranges(
+                        &input.value(),
+                        &self.find.matches,
+                        &self.find.replacement,
+                        selection(&input).end,
+                    );
+                    input.set_value(&text);
+                    select(&input, caret, caret);
+                    self.document_changed(false);
+                    self.view
+                        .notify(format!("Replaced {count} matches"), now_ms());
+                }
+            }
+            AppEvent::Action(Action::SortLines) => {
+                let (lines, _) = self.transform_lines(sort_lines);
+                self.view.notify(format!("Sorted {lines} lines"), now_ms());
+            }
+            AppEvent::Action(Action::UniqueLines) => {
+                let (before, after) = self.transform_lines(unique_lines);
+                self.view.notify(
+                    format!("Removed {} duplicate lines", before - after),
+                    now_ms(),
+                );
+            }
+            AppEvent::Action(Action::Uppercase) => self.transform_selection(str::to_uppercase),
+            AppEvent::Action(Action::Lowercase) => self.transform_selection(str::to_lowercase),
+            AppEvent::Action(Action::TitleCase) => self.transform_selection(title_case),
+            AppEvent::Action(Action::Base64Encode) => self.transform_selection(base64_encode),
+            AppEvent::Action(Action::Base64Decode) => self.try_transform_selection(base64_decode),
+            AppEvent::Action(Action::UrlEncode) => self.transform_selection(url_encode),
+            AppEvent::Action(Action::UrlDecode) => self.try_transform_selection(url_decode),
+            AppEvent::Action(Action::ToggleComment) => self.toggle_comment(),
+            AppEvent::Action(action @ (Action::MoveLinesUp | Action::MoveLinesDown)) => {
+                let up = action == Action::MoveLinesUp;
+                self.rearrange_lines(|text, block| move_lines(text, block, up));
+            }
+            AppEvent::Action(Action::DuplicateLines) => {
+                self.rearrange_lines(|text, block| Some(duplicate_lines(text, block)));
+            }
+            AppEvent::Action(action @ (Action::PrettifyJson | Action::MinifyJson)) => {
+                self.reformat_json(action == Action::PrettifyJson);
+            }
+            AppEvent::Action(action @ (Action::FindNext | Action::FindPrevious)) => {
+                let input = self.text_input.borrow();
+                let input = input.as_ref().unwrap();
+                match self
+                    .find
+                    .step(&selection(input), action == Action::FindNext)
+                {
+                    Some(found) => select(input, found.start, found.end),
+                    None => self.view.notify("No matches", now_ms()),
+                }
+            }
+            AppEvent::Action(Action::ToggleFindCase) => {
+                self.find.case_sensitive = !self.find.case_sensitive;
+                self.find
+                    .update(&self.text_input.borrow().as_ref().unwrap().value());
+            }
+            AppEvent::Action(Action::ToggleFindRegex) => {
+                self.find.regex = !self.find.regex;
+                self.find
+                    .update(&self.text_input.borrow().as_ref().unwrap().value());
+            }
+            AppEvent::FindQuery(query) => {
+                self.find.query = query;
+                self.find
+                    .update(&self.text_input.borrow().as_ref().unwrap().value());
+            }
+            AppEvent::Replacement(replacement) => self.find.replacement = replacement,
+            AppEvent::Action(Action::ToggleWhitespace) => {
+                self.config.show_whitespace = !self.config.show_whitespace;
+            }
+            AppEvent::Action(Action::ToggleTrailingWhitespace) => {
+                self.config.flag_trailing_whitespace = !self.config.flag_trailing_whitespace;
+            }
+            AppEvent::Action(Action::ToggleRuler) => {
+                self.config.show_ruler = !self.config.show_ruler;
+            }
+            AppEvent::Action(Action::ToggleLineNumbers) => {
+                self.config.line_numbers = !self.config.line_numbers;
+            }
+            AppEvent::Action(Action::SplitSideBySide) => self.view.split(Direction::Horizontal),
+            AppEvent::Action(Action::SplitStacked) => self.view.split(Direction::Vertical),
+            AppEvent::Action(Action::CloseSplit) => self.view.unsplit(),
+            AppEvent::Action(Action::SwitchPane) => {
+                if self.view.split.is_some() {
+                    self.view.active = 1 - self.view.active;
+                }
+            }
+            AppEvent::Action(Action::ToggleHelp) => self.help.open = !self.help.open,
+            AppEvent::Action(Action::CloseHelp) => self.help.open = false,
+            AppEvent::Action(Action::ToggleAutoClose) => {
+                self.config.auto_close = !self.config.auto_close;
+                self.view.notify(
+                    if self.config.auto_close {
+                        "Auto-close on"
+                    } else {
+                        "Auto-close off"
+                    },
+                    now_ms(),
+                );
+            }
+            AppEvent::Action(Action::CycleLineSpacing) => {
+                self.config.line_spacing = self.config.line_spacing.next();
+                self.view.notify(
+                    format!("Line spacing: {}", self.config.line_spacing.name()),
+                    now_ms(),
+                );
+            }
+            AppEvent::Action(Action::ToggleMinimap) => self.config.minimap = !self.config.minimap,
+            AppEvent::Action(action @ (Action::UseLf | Action::UseCrlf)) => {
+                self.config.line_ending = if action == Action::UseLf {
+                    LineEnding::Lf
+                } else {
+                    LineEnding::Crlf
+                };
+                self.schedule_save();
+                self.view.notify(
+                    format!(
+                        "Lines will be saved with {} endings",
+                        self.config.line_ending.label()
+                    ),
+                    now_ms(),
+                );
+            }
+            AppEvent::Action(Action::ToggleFrameStats) => self.stats.visible = !self.stats.visible,
+            AppEvent::Action(Action::ToggleFullscreen) => toggle_fullscreen(&self.config.canvas_id),
+            AppEvent::Action(Action::ToggleTypewriter) => {
+                self.config.typewriter = !self.config.typewriter;
+                self.view.pane_mut().follow_caret = true;
+            }
+            AppEvent::Action(Action::ToggleWrap) => {
+                self.config.wrap = !self.config.wrap;
+                self.view.pane_mut().follow_caret = true;
+                store_flag(&self.storage, WRAP_STORAGE_KEY, self.config.wrap);
+            }
+            AppEvent::Action(Action::ToggleHighContrast) => {
+                self.config.high_contrast = !self.config.high_contrast;
+                self.config.theme = self.config.theme_for(
+                    &Config::query_params_or_empty(),
+                    prefers_dark_color_scheme(),
+                );
+                terminal
+                    .backend_mut()
+                    .post_processor_mut()
+                    .set_effects(self.effects());
+                store_flag(
+                    &self.storage,
+                    HIGH_CONTRAST_STORAGE_KEY,
+                    self.config.high_contrast,
+                );
+            }
+            AppEvent::Action(Action::CycleTheme) => {
+                let (theme, high_contrast, name) = if self.config.high_contrast {
+                    (Theme::DARK, false, "dark")
+                } else if self.config.theme == Theme::DARK {
+                    (Theme::LIGHT, false, "light")
+                } else if self.config.theme == Theme::LIGHT {
+                    (Theme::HIGH_CONTRAST, true, "high contrast")
+                } else {
+                    (Theme::DARK, false, "dark")
+                };
+                self.config.theme = theme;
+                // A theme picked here stays when the system color scheme changes.
+                self.config.follow_color_scheme = false;
+                if high_contrast != self.config.high_contrast {
+                    self.config.high_contrast = high_contrast;
+                    terminal
+                        .backend_mut()
+                        .post_processor_mut()
+                        .set_effects(self.effects());
+                    store_flag(&self.storage, HIGH_CONTRAST_STORAGE_KEY, high_contrast);
+                }
+                self.view.notify(format!("Theme: {name}"), now_ms());
+            }
+            AppEvent::Action(Action::OpenPalette) => {
+                self.palette = Palette {
+                    open: true,
+                    ..Palette::default()
+                };
+                self.palette_input.borrow().as_ref().unwrap().set_value("");
+                self.focus_input();
+            }
+            AppEvent::Action(Action::ClosePalette) => {
+                self.palette.open = false;
+                self.focus_input();
+            }
+            AppEvent::Action(action @ (Action::PaletteUp | Action::PaletteDown)) => {
+                self.palette.step(action == Action::PaletteDown);
+            }
+            AppEvent::Action(Action::RunPaletteCommand) => {
+                self.palette.open = false;
+                self.focus_input();
+                if let Some(command) = self.palette.selected() {
+                    let _ = self.proxy.send_event(AppEvent::Action(command.action));
+                }
+            }
+            AppEvent::Action(Action::OpenGoToLine) => {
+                self.go_to_line = GoToLine {
+                    open: true,
+                    ..GoToLine::default()
+                };
+                self.go_to_line_input
+                    .borrow()
+                    .as_ref()
+                    .unwrap()
+                    .set_value("");
+                self.focus_input();
+            }
+            AppEvent::Action(Action::CloseGoToLine) => {
+                self.go_to_line.open = false;
+                self.focus_input();
+            }
+            AppEvent::Action(Action::ConfirmGoToLine) => {
+                let input = self.text_input.borrow().clone().unwrap();
+                let text = input.value();
+                match self.go_to_line.target(text.split('\n').count()) {
+                    Some(line) => {
+                        let offset = line_start(&text, line);
+                        select(&input, offset, offset);
+                        self.go_to_line.open = false;
+                        self.focus_input();
+                        self.announce(&format!("Line {}", line + 1));
+                    }
+                    None => self.view.notify("Enter a line number", now_ms()),
+                }
+            }
+            AppEvent::GoToLineInput(input) => self.go_to_line.input = input,
+            AppEvent::Action(action @ (Action::NewDocument | Action::ResetToSample)) => {
+                self.confirm.pending = Some(action);
+                self.confirm_input.borrow().as_ref().unwrap().set_value("");
+                self.focus_input();
+            }
+            AppEvent::Action(Action::OpenFile) => open_file(self.proxy.clone()),
+            AppEvent::Action(Action::ToggleDiff) => {
+                self.view.diff_scroll = match self.view.diff_scroll {
+                    Some(_) => None,
+                    None => Some(0),
+                };
+            }
+            AppEvent::Action(Action::MoveCaret(motion)) => self.move_caret(motion, false),
+            AppEvent::Action(Action::ExtendSelection(motion)) => self.move_caret(motion, true),
+            AppEvent::Action(Action::Confirm) => self.answer_confirm(true),
+            AppEvent::Action(Action::CancelConfirm) => self.answer_confirm(false),
+            AppEvent::ConfirmInput(input) => {
+                self.confirm_input.borrow().as_ref().unwrap().set_value("");
+                match input.trim().to_lowercase().as_str() {
+                    "y" => self.answer_confirm(true),
+                    "n" => self.answer_confirm(false),
+                    _ => {}
+                }
+            }
+            AppEvent::PaletteFilter(filter) => {
+                self.palette.filter = filter;
+                self.palette.selected = 0;
+            }
+            AppEvent::DarkColorScheme(dark) => {
+                if self.config.follow_color_scheme {
+                    self.config.theme = self
+                        .config
+                        .theme_for(&Config::query_params_or_empty(), dark);
+                }
+            }
+            AppEvent::ReducedMotion(reduced_motion) => {
+                self.view.reduced_motion = reduced_motion;
+                terminal
+                    .backend_mut()
+                    .post_processor_mut()
+                    .set_reduced_motion(reduced_motion);
+            }
+            AppEvent::FileLoaded { name, .. } if self.config.read_only => {
+                self.view.notify(
+                    format!("Can't open {name}, the document is read-only"),
+                    now_ms(),
+                );
+            }
+            AppEvent::FileLoaded {
+                name,
+                contents,
+                handle,
+            } => {
+                let input = self.text_input.borrow().clone().unwrap();
+                input.set_value(&contents);
+                let _ = input.set_selection_range(0, 0);
+                self.config.line_ending = LineEnding::detect(&contents);
+                self.document_changed(false);
+                self.mark_saved(&input.value());
+                if let Some(language) = Language::from_file_name(&name) {
+                    self.config.language = Some(language);
+                }
+                self.view.notify(format!("Loaded {name}"), now_ms());
+                self.file_name = Some(name);
+                self.file_handle = handle;
+            }
+            AppEvent::FileSaved {
+                handle,
+                saved,
+                auto,
+            } => {
+                let name = handle.name();
+                if !auto {
+                    self.view.notify(format!("Saved {name}"), now_ms());
+                }
+                self.file_name = Some(name);
+                self.file_handle = Some(handle);
+                // The document may have changed while it was being written.
+                self.dirty = self.text_input.borrow().as_ref().unwrap().value() != saved;
+                self.saved = saved;
+                if self.pending_save.is_none() {
+                    self.save_state = SaveState::Saved;
+                }
+            }
+            AppEvent::Paste(_) if self.config.read_only => {}
+            AppEvent::Paste(text) => {
+                let started = now_ms();
+                let input = self.text_input.borrow().clone().unwrap();
+                // Inserting the whole paste at once replaces the selection and puts
+                // the caret after it, without a separate `input` event.
+                let range = selection(&input);
+                let _ = input.set_range_text_with_start_and_end_and_selection_mode(
+                    &text,
+                    range.start,
+                    range.end,
+                    SelectionMode::End,
+                );
+                self.document_changed(false);
+                self.paste_started = Some((started, text.len()));
+            }
+            AppEvent::Action(Action::Newline) if self.config.read_only => {}
+            AppEvent::Action(Action::Newline) => self.newline(),
+            AppEvent::TypedPair(_) if self.config.read_only => {}
+            AppEvent::TypedPair(typed) => self.type_pair(typed),
+            AppEvent::Edited => self.document_changed(true),
+            // What the demo typed becomes the document, and is only recorded
+            // from here on. It isn't saved until the user edits it, so that
+            // reloading before then runs the demo again rather than keeping
+            // however much of the sample it had typed.
+            AppEvent::Interacted => {
+                if self.demo.take().is_some() {
+                    self.document_changed(false);
+                    self.pending_save = None;
+                    self.save_state = SaveState::Idle;
+                }
+            }
+            AppEvent::Composing(text) => self.composition = Some(text),
+            AppEvent::CompositionEnded => {
+                self.composition = None;
+                self.document_changed(true);
+            }
+            AppEvent::Ready { line_ending } => {
+                self.config.line_ending = line_ending;
+                if self.config.demo {
+                    self.text_input.borrow().as_ref().unwrap().set_value("");
+                    self.demo = Some(Demo {
+                        typed: 0,
+                        next_at: now_ms() + self.config.demo_ms,
+                    });
+                }
+                let snapshot = self.snapshot();
+                self.mark_saved(&snapshot.text);
+                self.history.record(snapshot, now_ms(), false);
+            }
+            AppEvent::Action(Action::Undo | Action::Redo) if self.config.read_only => {}
+            AppEvent::Action(action @ (Action::Undo | Action::Redo)) => {
+                let snapshot = if action == Action::Undo {
+                    self.history.undo()
+                } else {
+                    self.history.redo()
+                };
+                match snapshot {
+                    Some(snapshot) => {
+                        let input = self.text_input.borrow().clone().unwrap();
+                        input.set_value(&snapshot.text);
+                        select(&input, snapshot.selection.start, snapshot.selection.end);
+                        self.document_changed(false);
+                    }
+                    None if action == Action::Undo => self.view.notify("Nothing to undo", now_ms()),
+                    None => self.view.notify("Nothing to redo", now_ms()),
+                }
+            }
+            AppEvent::Message(text) => self.view.notify(text, now_ms()),
+            AppEvent::FontLoaded { name, data } => match LoadedFont::new(name, data) {
+                Some(font) => self.fonts.borrow_mut().as_mut().unwrap().primary.push(font),
+                None => log::warn!("{name} is not a usable font"),
+            },
+            AppEvent::VisibilityChanged { hidden } => {
+                self.hidden = hidden;
+                if hidden && self.pending_save.take().is_some() {
+                    self.auto_save();
+                }
+            }
+        }
+        self.force_redraw = true;
+
+        if !self.hidden {
+            self.window.borrow().as_ref().unwrap().request_redraw();
+        }
+    }
+
+    fn window_event(
+        &mut self,
+        _event_loop: &winit::event_loop::ActiveEventLoop,
+        _window_id: winit::window::WindowId,
+        event: winit::event::WindowEvent,
+    ) {
+        // The terminal is borrowed through its own handle so handlers below can still
+        // take `&mut self`.
+        let backend = self.backend.clone();
+        let mut terminal = backend.borrow_mut();
+        let Some(terminal) = terminal.as_mut() else {
+            return;
+        };
+
+        match event {
+            WindowEvent::Focused(true) => {
+                self.force_redraw = true;
+                self.focus_input();
+                self.window
+                    .borrow()
+                    .as_ref()
+                    .unwrap()
+                    .set_prevent_default(false);
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = position;
+                if let Some(pane) = self.scrollbar_drag
+                    && let Some(cell) = self.cell_at(terminal, self.cursor)
+                {
+                    let text = self.text_input.borrow().as_ref().unwrap().value();
+                    self.relayout(&text);
+                    self.view.panes[pane].scroll_to_scrollbar(&self.cache.lines, cell.y);
+                    self.force_redraw = true;
+                } else if let Some(anchor) = self.drag_anchor
+                    && let Some(focus) = self.offset_under_cursor(terminal)
+                {
+                    select(self.text_input.borrow().as_ref().unwrap(), anchor, focus);
+                }
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => match state {
+                ElementState::Pressed => {
+                    if let Some(cell) = self.cell_at(terminal, self.cursor)
+                        && let Some(minimap) = self.view.minimap_area
+                        && minimap.contains(cell)
+                    {
+                        self.jump_to_minimap_row(cell.y - minimap.y, minimap.height);
+                        self.force_redraw = true;
+                    } else if let Some(cell) = self.cell_at(terminal, self.cursor)
+                        && self.view.diff_scroll.is_none()
+                        && let Some(pane) = self.view.scrollbar_at(cell)
+                    {
+                        self.scrollbar_drag = Some(pane);
+                        let text = self.text_input.borrow().as_ref().unwrap().value();
+                        self.relayout(&text);
+                        self.view.panes[pane].scroll_to_scrollbar(&self.cache.lines, cell.y);
+                        self.force_redraw = true;
+                    } else {
+                        // Clicking a pane makes it the one the caret is followed in.
+                        if let Some(pane) = self
+                            .cell_at(terminal, self.cursor)
+                            .and_then(|cell| self.view.pane_at(cell))
+                        {
+                            self.view.active = pane;
+                        }
+                        if let Some(offset) = self.offset_under_cursor(terminal) {
+                            self.click(offset);
+                        }
+                    }
+                }
+                ElementState::Released => {
+                    self.drag_anchor = None;
+                    self.scrollbar_drag = None;
+                }
+            },
+            WindowEvent::MouseWheel { delta, .. } => {
+                if let Some((rows, cols)) = self.wheel_cells(terminal, delta) {
+                    if let Some(scroll) = &mut self.view.diff_scroll {
+                        // The unsaved changes cover the panes while they are shown.
+                        *scroll = scroll.saturating_add_signed(rows);
+                    } else {
+                        // The wheel scrolls whichever pane is under the pointer.
+                        let pane = self
+                            .cell_at(terminal, self.cursor)
+                            .and_then(|cell| self.view.pane_at(cell))
+                            .unwrap_or(self.view.active);
+                        self.view.panes[pane].scroll_by(rows, cols);
+                    }
+                    self.force_redraw = true;
+                }
+            }
+            WindowEvent::ScaleFactorChanged { .. } => {
+                self.pending_resize = Some(self.window.borrow().as_ref().unwrap().inner_size());
+            }
+            WindowEvent::Resized(size) => {
+                // Resizes are coalesced into the next frame so dragging the window doesn't
+                // reconfigure the surface on every tick.
+                self.pending_resize = Some(size);
+            }
+            WindowEvent::RedrawRequested => {
+                if self.pending_save.is_some_and(|save_at| save_at <= now_ms()) {
+                    self.pending_save = None;
+                    self.auto_save();
+                    // The status bar's save indicator changes with the same text.
+                    self.force_redraw = true;
+                }
+                if self
+                    .demo
+                    .as_ref()
+                    .is_some_and(|demo| demo.next_at <= now_ms())
+                {
+                    self.step_demo();
+                }
+
+                if let Some(size) = self.pending_resize.take() {
+                    self.zero_sized = size.width == 0 || size.height == 0;
+                    terminal
+                        .backend_mut()
+                        .resize(size.width.max(1), size.height.max(1));
+                    self.force_redraw = true;
+                }
+
+                if self.zero_sized {
+                    return;
+                }
+
+                // Frames that come early are skipped, keeping any forced redraw for
+                // the next one. The next frame is still requested below.
+                let now = now_ms();
+                let due = self.config.max_fps.is_none_or(|max_fps| {
+                    now - self.last_render >= 1000.0 / max_fps as f64 - FRAME_SLACK_MS
+                });
+                if due {
+                    self.last_render = now;
+                    let force = std::mem::take(&mut self.force_redraw);
+                    self.redraw(terminal, force);
+                }
+            }
+            _ => {}
+        }
+
+        // Stop scheduling frames while the page is in the background. A forced redraw
+        // is queued once it becomes visible again.
+        if !self.hidden {
+            self.window.borrow().as_ref().unwrap().request_redraw();
+        }
+    }
+}
+
+impl App {
+    pub fn new(proxy: EventLoopProxy<AppEvent>) -> Self {
+        Self {
+            config: Config::from_query(),
+            view: ViewState::default(),
+            cache: RenderCache::default(),
+            force_redraw: true,
+            pending_resize: None,
+            zero_sized: false,
+            hidden: false,
+            cursor: PhysicalPosition::default(),
+            wheel_remainder: (0.0, 0.0),
+            drag_anchor: None,
+            scrollbar_drag: None,
+            composition: None,
+            last_click: None,
+            click_count: 0,
+            find: FindBar::default(),
+            palette: Palette::default(),
+            go_to_line: GoToLine::default(),
+            confirm: Confirm::default(),
+            help: Help::default(),
+            stats: FrameStats::default(),
+            history: History::default(),
+            file_name: None,
+            file_handle: None,
+            saved: String::new(),
+            dirty: false,
+            last_render: 0.0,
+            paste_started: None,
+            drawn: false,
+            storage: local_storage(),
+            pending_save: None,
+            demo: None,
+            save_state: SaveState::default(),
+            proxy,
+            window: Rc::default(),
+            fonts: Rc::default(),
+            backend: Rc::default(),
+            backends: Rc::new(Cell::new(wgpu::Backends::BROWSER_WEBGPU)),
+            text_input: Rc::default(),
+            find_input: Rc::default(),
+            replace_input: Rc::default(),
+            palette_input: Rc::default(),
+            go_to_line_input: Rc::default(),
+            confirm_input: Rc::default(),
+            live_region: Rc::default(),
+            announced: None,
+        }
+    }
+
+    fn effects(&self) -> Effects {
+        Effects {
+            effect: self.config.effect,
+            crt_settings: self.config.crt_settings,
+            reduced_motion: self.view.reduced_motion,
+            high_contrast: self.config.high_contrast && !self.config.high_contrast_effects,
+            adjustment: self.config.adjustment,
+        }
+    }
+
+    /// Rebuilds the terminal after its device is lost. The document and
+    /// selection live in the textarea, so only the GPU side needs to be
+    /// recreated.
+    fn reconnect(&mut self, reason: &str) {
+        log::warn!("The GPU device was lost: {reason}");
+        // Dropping the terminal also stops events from reaching it until the new
+        // one is ready.
+        if self.backend.borrow_mut().take().is_none() {
+            return;
+        }
+        let canvas = self
+            .window
+            .borrow()
+            .as_ref()
+            .and_then(|window| window.canvas());
+        let fonts = self.fonts.borrow().clone();
+        // The old terminal is gone either way, so without these there is
+        // nothing left to draw with.
+        let (Some(canvas), Some(fonts)) = (canvas, fonts) else {
+            show_overlay(
+                &self.config.canvas_id,
+                &format!("The GPU was lost: {reason}"),
+            );
+            return;
+        };
+
+        let overlay = show_overlay(&self.config.canvas_id, "Reconnecting to the GPU…");
+        let size = self.window.borrow().as_ref().unwrap().inner_size();
+        let size = Dimensions {
+            width: NonZeroU32::new(size.width).unwrap_or(NonZeroU32::MIN),
+            height: NonZeroU32::new(size.height).unwrap_or(NonZeroU32::MIN),
+        };
+        let effects = self.effects();
+        self.force_redraw = true;
+
+        let window = self.window.clone();
+        let backend = self.backend.clone();
+        let backends = self.backends.get();
+        let custom_shader = self.config.custom_shader.clone();
+        let proxy = self.proxy.clone();
+        let canvas_id = self.config.canvas_id.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let result = build_terminal(
+                canvas,
+                &fonts,
+                backends,
+                size,
+                effects,
+                custom_shader,
+                proxy,
+            )
+            .await;
+            if let Some(overlay) = overlay {
+                overlay.remove();
+            }
+            match result {
+                Ok(terminal) => {
+                    *backend.borrow_mut() = Some(terminal);
+                    // Frames stop being scheduled while there is no terminal.
+                    window.borrow().as_ref().unwrap().request_redraw();
+                }
+                Err(err) => {
+                    log::error!("{err:#}");
+                    show_overlay(&canvas_id, &format!("The GPU was lost: {err:#}"));
+                }
+            }
+        });
+    }
+
+    /// Rewrites the lines the selection touches, or the whole document when
+    /// nothing is selected. A selection is widened to cover the rewritten
+    /// lines, and a bare caret stays where it was. Returns the number of
+    /// lines before and after.
+    fn transform_lines(&mut self, transform: fn(&str) -> String) -> (usize, usize) {
+        let input = self.text_input.borrow().clone().unwrap();
+        let value = input.value();
+        let range = selection(&input);
+        let bytes = if range.is_empty() {
+            0..value.len()
+        } else {
+            line_span(
+                &value,
+                byte_at(&value, range.start)..byte_at(&value, range.end),
+            )
+        };
+
+        let lines = &value[bytes.clone()];
+        let transformed = transform(lines);
+        let counts = (lines.lines().count(), transformed.lines().count());
+        if transformed == lines {
+            return counts;
+        }
+
+        input.set_value(&format!(
+            "{}{transformed}{}",
+            &value[..bytes.start],
+            &value[bytes.end..]
+        ));
+        if range.is_empty() {
+            select(&input, range.start, range.start);
+        } else {
+            let start = value[..bytes.start].encode_utf16().count() as u32;
+            let end = start + transformed.encode_utf16().count() as u32;
+            select(&input, start, end);
+        }
+        self.document_changed(false);
+        counts
+    }
+
+    /// Rewrites the selected text and selects the result. Case mappings can
+    /// change the length of the text, e.g. "ß" to "SS", so the textarea selects
+    /// what was inserted rather than keeping the old offsets.
+    fn transform_selection(&mut self, transform: fn(&str) -> String) {
+        self.try_transform_selection(|text| Ok(transform(text)));
+    }
+
+    /// Like [`Self::transform_selection`], but leaves the text alone and
+    /// reports why if `transform` fails.
+    fn try_transform_selection(&mut self, transform: impl FnOnce(&str) -> Result<String, String>) {
+        let input = self.text_input.borrow().clone().unwrap();
+        let range = selection(&input);
+        if range.is_empty() {
+            self.view.notify("Nothing is selected", now_ms());
+            return;
+        }
+
+        let value = input.value();
+        let bytes = byte_at(&value, range.start)..byte_at(&value, range.end);
+        let transformed = match transform(&value[bytes.clone()]) {
+            Ok(transformed) => transformed,
+            Err(err) => {
+                self.view
+                    .notify(format!("Can't convert the selection: {err}"), now_ms());
+                return;
+            }
+        };
+        if transformed == value[bytes] {
+            return;
+        }
+
+        let _ = input.set_range_text_with_start_and_end_and_selection_mode(
+            &transformed,
+            range.start,
+            range.end,
+            SelectionMode::Select,
+        );
+        self.document_changed(false);
+    }
+
+    /// Toggles comments on the lines the selection touches. A selection is
+    /// widened to cover the lines afterwards, and a caret stays on the same
+    /// character.
+    fn toggle_comment(&mut self) {
+        let input = self.text_input.borrow().clone().unwrap();
+        let value = input.value();
+        let range = selection(&input);
+        let bytes = line_span(
+            &value,
+            byte_at(&value, range.start)..byte_at(&value, range.end),
+        );
+        let lines = &value[bytes.clone()];
+        let toggled = toggle_comment(lines, &self.config.comment_prefix);
+        if toggled == lines {
+            return;
+        }
+
+        let start = value[..bytes.start].encode_utf16().count() as u32;
+        let end = start + lines.encode_utf16().count() as u32;
+        let _ = input.set_range_text_with_start_and_end(&toggled, start, end);
+        let toggled_end = start + toggled.encode_utf16().count() as u32;
+        if range.is_empty() {
+            // Moves with the text after it, unless that was uncommented away.
+            let caret = (range.start + toggled_end).saturating_sub(end).max(start);
+            select(&input, caret, caret);
+        } else {
+            select(&input, start, toggled_end);
+        }
+        self.document_changed(false);
+    }
+
+    /// Rewrites the document around the lines the selection touches. The
+    /// selection follows the lines to wherever `edit` selects them, so
+    /// moving them again moves the same lines.
+    fn rearrange_lines(&mut self, edit: impl FnOnce(&str, Range<usize>) -> Option<Insertion>) {
+        let input = self.text_input.borrow().clone().unwrap();
+        let value = input.value();
+        let range = selection(&input);
+        let block = line_span(
+            &value,
+            byte_at(&value, range.start)..byte_at(&value, range.end),
+        );
+        let Some(moved) = edit(&value, block.clone()) else {
+            return;
+        };
+
+        let start = value[..moved.replace.start].encode_utf16().count() as u32;
+        let end = start + value[moved.replace.clone()].encode_utf16().count() as u32;
+        let _ = input.set_range_text_with_start_and_end(&moved.text, start, end);
+        let old_start = value[..block.start].encode_utf16().count() as u32;
+        let new_start = start + moved.text[..moved.select.start].encode_utf16().count() as u32;
+        let shift = |offset: u32| offset + new_start - old_start;
+        select(&input, shift(range.start), shift(range.end));
+        self.document_changed(false);
+    }
+
+    /// Reformats the selection as JSON, or the whole document if nothing is
+    /// selected. Invalid JSON is left alone and the error's location reported.
+    fn reformat_json(&mut self, pretty: bool) {
+        let input = self.text_input.borrow().clone().unwrap();
+        let value = input.value();
+        let mut range = selection(&input);
+        let whole = range.is_empty();
+        if whole {
+            range = 0..value.encode_utf16().count() as u32;
+        }
+
+        let bytes = byte_at(&value, range.start)..byte_at(&value, range.end);
+        let formatted = match reformat_json(&value, bytes.clone(), pretty) {
+            Ok(formatted) => formatted,
+            Err(err) => {
+                self.view.notify(err, now_ms());
+                return;
+            }
+        };
+        if formatted == value[bytes] {
+            return;
+        }
+
+        let _ = input.set_range_text_with_start_and_end_and_selection_mode(
+            &formatted,
+            range.start,
+            range.end,
+            if whole {
+                SelectionMode::Start
+            } else {
+                SelectionMode::Select
+            },
+        );
+        self.document_changed(false);
+    }
+
+    /// Types a bracket or quote, closing it if auto-close is on. It is typed
+    /// like any other character otherwise, so it shares an undo step with the
+    /// typing around it.
+    fn type_pair(&mut self, typed: char) {
+        let input = self.text_input.borrow().clone().unwrap();
+        let value = input.value();
+        let range = selection(&input);
+        let bytes = byte_at(&value, range.start)..byte_at(&value, range.end);
+        let insertion = self
+            .config
+            .auto_close
+            .then(|| auto_close(&value, bytes.clone(), typed))
+            .flatten()
+            .unwrap_or_else(|| Insertion {
+                replace: bytes,
+                text: typed.to_string(),
+                select: typed.len_utf8()..typed.len_utf8(),
+            });
+
+        let start = value[..insertion.replace.start].encode_utf16().count() as u32;
+        let end = start + value[insertion.replace].encode_utf16().count() as u32;
+        let _ = input.set_range_text_with_start_and_end(&insertion.text, start, end);
+        let utf16 = |bytes: usize| start + insertion.text[..bytes].encode_utf16().count() as u32;
+        select(
+            &input,
+            utf16(insertion.select.start),
+            utf16(insertion.select.end),
+        );
+        self.document_changed(true);
+    }
+
+    /// Breaks the line at the caret, carrying its indentation over to the new
+    /// one, as a single undo step.
+    fn newline(&mut self) {
+        let input = self.text_input.borrow().clone().unwrap();
+        let value = input.value();
+        let range = selection(&input);
+        let (text, inside) = newline(
+            &value,
+            byte_at(&value, range.start),
+            self.config.brace_indent.then_some(self.config.tab_width),
+        );
+
+        let _ = input.set_range_text_with_start_and_end(&text, range.start, range.end);
+        let caret = range.start + text[..inside].encode_utf16().count() as u32;
+        select(&input, caret, caret);
+        self.document_changed(false);
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let input = self.text_input.borrow();
+        let input = input.as_ref().unwrap();
+        Snapshot {
+            text: input.value(),
+            selection: selection(input),
+        }
+    }
+
+    /// Restarts the wait for typing to pause before auto-saving.
+    fn schedule_save(&mut self) {
+        if self.config.auto_save {
+            self.pending_save = Some(now_ms() + self.config.auto_save_ms);
+            self.save_state = SaveState::Saving;
+        }
+    }
+
+    /// Saves to local storage, and writes the file back too if it was opened
+    /// or saved through a picker. Both use the document's own line ending, so
+    /// it is detected again when the file is next opened.
+    fn auto_save(&mut self) {
+        let value = self.text_input.borrow().as_ref().unwrap().value();
+        let text = self.config.line_ending.apply(&value);
+        save_to_storage(&mut self.storage, &text);
+        if let Some(handle) = self.file_handle.clone() {
+            // Still saving until the write finishes.
+            write_file(
+                Some(handle),
+                String::new(),
+                text.into_owned(),
+                value,
+                true,
+                self.proxy.clone(),
+            );
+        } else if self.storage.is_some() {
+            self.save_state = SaveState::Saved;
+        } else {
+            self.save_state = SaveState::Idle;
+        }
+    }
+
+    /// Updates everything derived from the document after its text changes.
+    /// Edits that were `typed` close together share an undo step.
+    fn document_changed(&mut self, typed: bool) {
+        let snapshot = self.snapshot();
+        self.dirty = snapshot.text != self.saved;
+        self.schedule_save();
+        // Whatever was there before has been checked already.
+        let new_text = self
+            .history
+            .current()
+            .map_or(snapshot.text.as_str(), |previous| {
+                inserted(&previous.text, &snapshot.text)
+            });
+        self.fonts
+            .borrow_mut()
+            .as_mut()
+            .unwrap()
+            .report_missing(new_text);
+        self.find.update(&snapshot.text);
+        self.history.record(snapshot, now_ms(), typed);
+    }
+
+    /// Closes the confirmation prompt, running the action it asked about if
+    /// the answer was yes.
+    fn answer_confirm(&mut self, confirmed: bool) {
+        let Some(action) = self.confirm.pending.take() else {
+            return;
+        };
+        self.focus_input();
+        if !confirmed {
+            return;
+        }
+        match action {
+            Action::NewDocument => {
+                let template = self.config.template.clone();
+                self.replace_document(&template);
+                self.file_name = None;
+                self.file_handle = None;
+                // The textarea normalizes line endings, so its value is what
+                // later edits are compared with.
+                let value = self.text_input.borrow().as_ref().unwrap().value();
+                self.mark_saved(&value);
+                self.document_changed(false);
+                self.view.notify("New document", now_ms());
+            }
+            // Still the same document, so it keeps its file and becomes
+            // unsaved, and the reset can be undone.
+            Action::ResetToSample => {
+                self.replace_document(SAMPLE_TEXT);
+                self.document_changed(false);
+                self.view.notify("Restored the sample text", now_ms());
+            }
+            _ => {}
+        }
+    }
+
+    /// Types the next grapheme of the sample, or once it has all been up for a
+    /// while, starts over from nothing. The textarea is changed directly so
+    /// the demo's typing isn't recorded or saved.
+    fn step_demo(&mut self) {
+        let Some(demo) = &mut self.demo else {
+            return;
+        };
+        if demo.typed == SAMPLE_TEXT.len() {
+            demo.typed = 0;
+        } else {
+            demo.typed += SAMPLE_TEXT[demo.typed..]
+                .graphemes(true)
+                .next()
+                .map_or(0, str::len);
+        }
+        let finished = demo.typed == SAMPLE_TEXT.len();
+        demo.next_at = now_ms()
+            + if finished {
+                DEMO_PAUSE_MS
+            } else {
+                self.config.demo_ms
+            };
+
+        let typed = &SAMPLE_TEXT[..demo.typed];
+        let input = self.text_input.borrow().clone().unwrap();
+        input.set_value(typed);
+        let end = typed.encode_utf16().count() as u32;
+        select(&input, end, end);
+    }
+
+    /// Swaps in `text` with the caret and every pane back at the top. The
+    /// caller records the change.
+    fn replace_document(&mut self, text: &str) {
+        let input = self.text_input.borrow().clone().unwrap();
+        input.set_value(text);
+        select(&input, 0, 0);
+        for pane in &mut self.view.panes {
+            pane.scroll_row = 0;
+            pane.scroll_col = 0;
+        }
+    }
+
+    /// Takes `text` as what is on disk, so the document is clean until it next
+    /// changes from it.
+    fn mark_saved(&mut self, text: &str) {
+        self.saved = text.to_string();
+        self.dirty = false;
+    }
+
+    /// Focuses the input that keystrokes should currently go to.
+    fn focus_input(&self) {
+        let result = if self.confirm.pending.is_some() {
+            self.confirm_input.borrow().as_ref().unwrap().focus()
+        } else if self.palette.open {
+            self.palette_input.borrow().as_ref().unwrap().focus()
+        } else if self.go_to_line.open {
+            self.go_to_line_input.borrow().as_ref().unwrap().focus()
+        } else {
+            match (self.find.open, self.find.replace_focused) {
+                (false, _) => self.text_input.borrow().as_ref().unwrap().focus(),
+                (true, false) => self.find_input.borrow().as_ref().unwrap().focus(),
+                (true, true) => self.replace_input.borrow().as_ref().unwrap().focus(),
+            }
+        };
+        if let Err(err) = result {
+            log::warn!("Failed to focus the input: {err:?}");
+        }
+    }
+
+    fn click(&mut self, offset: u32) {
+        let now = now_ms();
+        self.click_count = match self.last_click {
+            Some((at, last)) if last == offset && now - at <= MULTI_CLICK_MS => {
+                self.click_count % 3 + 1
+            }
+            _ => 1,
+        };
+        self.last_click = Some((now, offset));
+
+        let input = self.text_input.borrow().clone().unwrap();
+        let text = input.value();
+        self.relayout(&text);
+        let lines = &self.cache.lines;
+        let line = lines
+            .partition_point(|line| line.offset <= offset)
+            .saturating_sub(1);
+        let Some(row) = lines.get(line) else {
+            return;
+        };
+
+        match self.click_count {
+            1 => {
+                select(&input, offset, offset);
+                self.drag_anchor = Some(offset);
+            }
+            2 => {
+                let word = word_at(&text, row, offset);
+                select(&input, word.start, word.end);
+            }
+            _ => {
+                // Always take the newline with the line so repeated triple clicks
+                // select consistently.
+                let end = lines.get(line + 1).map_or_else(
+                    || row.offset + text[row.bytes.clone()].encode_utf16().count() as u32,
+                    |next| next.offset,
+                );
+                select(&input, row.offset, end);
+            }
+        }
+    }
+
+    /// Moves the caret through the rows the active pane last laid out, so that
+    /// with wrapping on it goes to the ends of the row on screen. Paging
+    /// scrolls by the same rows the caret moves, leaving it where it was on
+    /// screen.
+    fn move_caret(&mut self, motion: Motion, extend: bool) {
+        let input = self.text_input.borrow().clone().unwrap();
+        let text = input.value();
+        self.relayout(&text);
+        let range = selection(&input);
+        let backward = input
+            .selection_direction()
+            .ok()
+            .flatten()
+            .is_some_and(|direction| direction == "backward");
+        let (anchor, focus) = if backward {
+            (range.end, range.start)
+        } else {
+            (range.start, range.end)
+        };
+
+        let widths = self.config.widths();
+        let pane = &mut self.view.panes[self.view.active];
+        let rows = pane.rows(&self.cache.lines);
+        let caret = Caret::locate(&text, rows, focus, widths);
+        let target = match motion {
+            Motion::LineStart => rows[caret.row].offset,
+            Motion::LineEnd => row_end(&text, &rows[caret.row]),
+            Motion::DocumentStart => 0,
+            Motion::DocumentEnd => text.encode_utf16().count() as u32,
+            Motion::PageUp | Motion::PageDown => {
+                let page = pane.visible_rows().max(1);
+                let row = if motion == Motion::PageUp {
+                    caret.row.saturating_sub(page)
+                } else {
+                    (caret.row + page).min(rows.len() - 1)
+                };
+                let target = offset_in_row(&text, &rows[row], caret.cell, widths);
+                pane.scroll_by(row as isize - caret.row as isize, 0);
+                target
+            }
+        };
+
+        select(&input, if extend { anchor } else { target }, target);
+    }
+
+    /// Lays `text` out again before the rows from the last frame are used. A
+    /// frame may have been skipped since the text last changed, and its rows
+    /// would then point past the end of the text or into a character.
+    fn relayout(&mut self, text: &str) {
+        self.cache.index_lines(text);
+        let widths = self.config.widths();
+        for pane in &mut self.view.panes {
+            pane.rewrap(text, &self.cache.lines, widths);
+        }
+    }
+
+    /// Has screen readers read out `text`, which isn't shown anywhere.
+    fn announce(&self, text: &str) {
+        if let Some(region) = self.live_region.borrow().as_ref() {
+            region.set_text_content(Some(text));
+        }
+    }
+
+    /// Scrolls the lines a row of the minimap stands for to the middle of the
+    /// editor.
+    fn jump_to_minimap_row(&mut self, row: u16, height: u16) {
+        let text = self.text_input.borrow().as_ref().unwrap().value();
+        self.relayout(&text);
+        let lines = &self.cache.lines;
+        let line = (row as usize * minimap_lines_per_row(lines.len(), height))
+            .min(lines.len().saturating_sub(1));
+        let pane = &mut self.view.panes[self.view.active];
+        let target = pane.rows(lines).partition_point(|row| row.line < line);
+        pane.scroll_row = target.saturating_sub(pane.visible_rows() / 2);
+        pane.follow_caret = false;
+    }
+
+    fn offset_under_cursor(&mut self, terminal: &Terminal<EditorBackend>) -> Option<u32> {
+        let cell = self.cell_at(terminal, self.cursor)?;
+        let text = self.text_input.borrow().as_ref()?.value();
+        self.relayout(&text);
+
+        // Drags keep selecting in the pane they started in, even once the
+        // pointer leaves it.
+        Some(
+            self.view
+                .pane()
+                .offset_at(&text, &self.cache.lines, cell, self.config.widths()),
+        )
+    }
+
+    /// Converts a wheel delta into whole rows and columns to scroll by,
+    /// carrying any fraction over to the next event.
+    fn wheel_cells(
+        &mut self,
+        terminal: &Terminal<EditorBackend>,
+        delta: MouseScrollDelta,
+    ) -> Option<(isize, isize)> {
+        let (x, y) = match delta {
+            MouseScrollDelta::LineDelta(x, y) => (x as f64, y as f64),
+            MouseScrollDelta::PixelDelta(position) => {
+                let cells = terminal.size().ok()?;
+                let pixels = self.window.borrow().as_ref()?.inner_size();
+                if pixels.width == 0 || pixels.height == 0 {
+                    return None;
+                }
+                (
+                    position.x * cells.width as f64 / pixels.width as f64,
+                    position.y * cells.height as f64 / pixels.height as f64,
+                )
+            }
+        };
+
+        // Positive deltas scroll towards the start of the document.
+        let (cols, rows) = &mut self.wheel_remainder;
+        *cols -= x;
+        *rows -= y;
+        let whole = (cols.trunc(), rows.trunc());
+        *cols -= whole.0;
+        *rows -= whole.1;
+
+        Some((whole.1 as isize, whole.0 as isize))
+    }
+
+    /// Converts a position in physical pixels to the terminal cell under it.
+    fn cell_at(
+        &self,
+        terminal: &Terminal<EditorBackend>,
+        position: PhysicalPosition<f64>,
+    ) -> Option<Position> {
+        let cells = terminal.size().ok()?;
+        let pixels = self.window.borrow().as_ref()?.inner_size();
+        if pixels.width == 0 || pixels.height == 0 {
+            return None;
+        }
+
+        Some(Position {
+            x: (position.x * cells.width as f64 / pixels.width as f64) as u16,
+            y: (position.y * cells.height as f64 / pixels.height as f64) as u16,
+        })
+    }
+
+    fn redraw(&mut self, terminal: &mut Terminal<EditorBackend>, mut force: bool) {
+        let Self {
+            config,
+            view,
+            cache,
+            find,
+            palette,
+            go_to_line,
+            confirm,
+            help,
+            stats,
+            file_name,
+            dirty,
+            save_state,
+            saved,
+            composition,
+            text_input,
+            paste_started,
+            drawn,
+            live_region,
+            announced,
+            ..
+        } = self;
+        let text_input = text_input.borrow();
+        let text_input = text_input.as_ref().unwrap();
+
+        let current = text_input.value();
+
+        let current_start = text_input.selection_start().ok().flatten();
+        let current_end = text_input.selection_end().ok().flatten();
+        let direction = text_input.selection_direction().ok().flatten();
+
+        if view
+            .message
+            .as_ref()
+            .is_some_and(|message| message.expires_at <= now_ms())
+        {
+            view.message = None;
+            force = true;
+        }
+        if let Some(message) = &view.message
+            && *announced != Some(message.expires_at)
+        {
+            *announced = Some(message.expires_at);
+            if let Some(region) = live_region.borrow().as_ref() {
+                region.set_text_content(Some(&message.text));
+            }
+        }
+
+        let frame_start = now_ms();
+        if stats.visible && stats.refresh_at <= frame_start {
+            stats.refresh_at = frame_start + FRAME_STATS_REFRESH_MS;
+            force = true;
+        }
+        if view.scrolling() {
+            force = true;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        (&current, current_start, current_end, &direction).hash(&mut hasher);
+        let frame = hasher.finish();
+        if !force
+            && cache.last_frame == Some(frame)
+            && !terminal.backend().post_processor().needs_update()
+        {
+            return;
+        }
+        if cache.last_frame != Some(frame) {
+            view.pane_mut().follow_caret = true;
+        }
+        cache.last_frame = Some(frame);
+
+        let text_len = current.encode_utf16().count();
+        let start = current_start.unwrap_or_default();
+        let end = current_end.unwrap_or(text_len as u32);
+
+        let backward = direction.is_some_and(|direction| direction == "backward");
+        let caret_offset = if backward { start } else { end };
+
+        let selection = selection_range(start, end);
+
+        if let Err(err) = terminal.draw(|f| {
+            f.render_stateful_widget(
+                Screen {
+                    config: &*config,
+                    text: &current,
+                    cache,
+                    selection,
+                    caret: caret_offset,
+                    find: &*find,
+                    palette: &*palette,
+                    go_to_line: &*go_to_line,
+                    confirm: &*confirm,
+                    help: &*help,
+                    stats: &*stats,
+                    file_name: file_name.as_deref().unwrap_or("untitled"),
+                    dirty: *dirty,
+                    save_state: *save_state,
+                    saved,
+                    composition: composition.as_deref(),
+                },
+                f.area(),
+                view,
+            )
+        }) {
+            log::error!("Failed to draw a frame: {err}");
+            // Tries again on the next frame rather than treating this one as
+            // drawn.
+            cache.last_frame = None;
+            return;
+        }
+        // Drawing flushes to the backend, which renders and presents the surface
+        // before returning.
+        let frame_end = now_ms();
+        stats.record(frame_start, frame_end);
+        if !std::mem::replace(drawn, true) {
+            hide_loading();
+        }
+        if let Some((started, bytes)) = paste_started.take() {
+            log::info!(
+                "Pasted {} KiB in {:.1} ms",
+                bytes / 1024,
+                frame_end - started
+            );
+        }
+    }
+}
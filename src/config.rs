@@ -0,0 +1,786 @@
+use ratatui::{
+    prelude::*,
+    symbols::border,
+};
+use web_sys::UrlSearchParams;
+
+use crate::{
+    edit::LineEnding,
+    gpu::Effect,
+    render::{
+        CellWidths,
+        MAX_TAB_WIDTH,
+    },
+    shader::{
+        Adjustment,
+        CrtSettings,
+    },
+    storage::{
+        BRIGHTNESS_STORAGE_KEY,
+        CONTRAST_STORAGE_KEY,
+        DEMO_TYPING_MS,
+        HIGH_CONTRAST_STORAGE_KEY,
+        SAVE_DEBOUNCE_MS,
+        WRAP_STORAGE_KEY,
+        stored_flag,
+        stored_number,
+    },
+    syntax::Language,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaretStyle {
+    #[default]
+    Block,
+    Bar,
+    Underline,
+}
+
+impl CaretStyle {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "block" => Some(Self::Block),
+            "bar" => Some(Self::Bar),
+            "underline" => Some(Self::Underline),
+            _ => None,
+        }
+    }
+
+    /// Draws the caret in the theme's caret color. A block is filled with it
+    /// and the glyph inside drawn in the background color, so it stays legible
+    /// over any syntax color.
+    pub fn render(self, area: Rect, buf: &mut Buffer, theme: Theme) {
+        let underline = Style::new().fg(theme.caret).underlined();
+        match self {
+            Self::Block if theme.reverse_video => buf.set_style(area, Style::new().reversed()),
+            Self::Block => buf.set_style(area, Style::new().fg(theme.background).bg(theme.caret)),
+            Self::Underline => buf.set_style(area, underline),
+            Self::Bar => {
+                // A bar would hide the glyph under it, so it is only drawn into
+                // blank cells and falls back to an underline over text.
+                if let Some(cell) = buf.cell_mut(area.as_position())
+                    && cell.symbol() == " "
+                {
+                    cell.set_symbol("▏").set_fg(theme.caret);
+                } else {
+                    buf.set_style(area, underline);
+                }
+            }
+        }
+    }
+}
+
+/// The line drawn around each editor pane.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BorderStyle {
+    #[default]
+    Rounded,
+    Plain,
+    Double,
+    Thick,
+    /// No border, leaving the whole pane for text.
+    None,
+}
+
+impl BorderStyle {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "rounded" => Some(Self::Rounded),
+            "plain" => Some(Self::Plain),
+            "double" => Some(Self::Double),
+            "thick" => Some(Self::Thick),
+            "none" => Some(Self::None),
+            _ => None,
+        }
+    }
+
+    pub fn set(self) -> Option<border::Set> {
+        match self {
+            Self::Rounded => Some(border::ROUNDED),
+            Self::Plain => Some(border::PLAIN),
+            Self::Double => Some(border::DOUBLE),
+            Self::Thick => Some(border::THICK),
+            Self::None => None,
+        }
+    }
+}
+
+/// How much room is left between rows of text. The terminal's cells are a
+/// fixed height, so spacing is made of blank rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineSpacing {
+    #[default]
+    Tight,
+    /// A blank row after each row of text.
+    Normal,
+    /// Two blank rows after each row of text.
+    Relaxed,
+}
+
+impl LineSpacing {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "tight" => Some(Self::Tight),
+            "normal" => Some(Self::Normal),
+            "relaxed" => Some(Self::Relaxed),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Tight => "tight",
+            Self::Normal => "normal",
+            Self::Relaxed => "relaxed",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::Tight => Self::Normal,
+            Self::Normal => Self::Relaxed,
+            Self::Relaxed => Self::Tight,
+        }
+    }
+
+    pub fn blank_rows(self) -> usize {
+        match self {
+            Self::Tight => 0,
+            Self::Normal => 1,
+            Self::Relaxed => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Theme {
+    pub background: Color,
+    pub foreground: Color,
+    pub selection: Color,
+    /// The color of selected text, or `None` to keep its syntax colors.
+    pub selection_foreground: Option<Color>,
+    pub caret: Color,
+    pub current_line: Color,
+    pub gutter: Color,
+    pub bold_selection: bool,
+    /// Draws the selection and a block caret in reverse video rather than in
+    /// their own colors.
+    pub reverse_video: bool,
+    pub find_match: Color,
+    pub find_match_foreground: Color,
+    /// The background of the column the ruler is drawn at.
+    pub ruler: Color,
+    pub trailing_whitespace: Color,
+    /// The background of the bracket beside the caret and its partner.
+    pub bracket_match: Color,
+    /// The color of a bracket beside the caret that has no partner.
+    pub unmatched_bracket: Color,
+    pub diff_added: Color,
+    pub diff_removed: Color,
+    /// The color of the `@@` line opening each stretch of changes.
+    pub diff_hunk: Color,
+}
+
+impl Theme {
+    pub const DARK: Self = Self {
+        background: Color::Rgb(24, 24, 24),
+        foreground: Color::Rgb(212, 212, 212),
+        selection: Color::Rgb(38, 79, 120),
+        selection_foreground: None,
+        caret: Color::Rgb(212, 212, 212),
+        current_line: Color::Rgb(40, 40, 40),
+        gutter: Color::Rgb(110, 110, 110),
+        bold_selection: false,
+        reverse_video: false,
+        find_match: Color::Yellow,
+        find_match_foreground: Color::Black,
+        ruler: Color::Rgb(55, 55, 55),
+        trailing_whitespace: Color::Rgb(110, 30, 30),
+        bracket_match: Color::Rgb(70, 70, 70),
+        unmatched_bracket: Color::Rgb(240, 80, 80),
+        diff_added: Color::Rgb(30, 75, 40),
+        diff_removed: Color::Rgb(90, 35, 35),
+        diff_hunk: Color::Cyan,
+    };
+    /// Pure white on black, with the selection in bold black on yellow so it
+    /// doesn't rely on telling shades apart.
+    pub const HIGH_CONTRAST: Self = Self {
+        background: Color::Rgb(0, 0, 0),
+        foreground: Color::Rgb(255, 255, 255),
+        selection: Color::Rgb(255, 255, 0),
+        selection_foreground: Some(Color::Rgb(0, 0, 0)),
+        caret: Color::Rgb(255, 255, 255),
+        current_line: Color::Rgb(0, 0, 0),
+        gutter: Color::Rgb(255, 255, 255),
+        bold_selection: true,
+        reverse_video: false,
+        find_match: Color::Rgb(0, 255, 255),
+        find_match_foreground: Color::Rgb(0, 0, 0),
+        ruler: Color::Rgb(0, 0, 160),
+        trailing_whitespace: Color::Rgb(255, 0, 255),
+        bracket_match: Color::Rgb(0, 0, 160),
+        unmatched_bracket: Color::Rgb(255, 64, 64),
+        diff_added: Color::Rgb(0, 100, 0),
+        diff_removed: Color::Rgb(160, 0, 0),
+        diff_hunk: Color::Rgb(0, 255, 255),
+    };
+    pub const LIGHT: Self = Self {
+        background: Color::Rgb(250, 250, 250),
+        foreground: Color::Rgb(36, 36, 36),
+        selection: Color::Rgb(173, 214, 255),
+        selection_foreground: None,
+        caret: Color::Rgb(36, 36, 36),
+        current_line: Color::Rgb(235, 235, 235),
+        gutter: Color::Rgb(140, 140, 140),
+        bold_selection: false,
+        reverse_video: false,
+        find_match: Color::Rgb(255, 213, 79),
+        find_match_foreground: Color::Rgb(36, 36, 36),
+        ruler: Color::Rgb(225, 225, 225),
+        trailing_whitespace: Color::Rgb(255, 200, 200),
+        bracket_match: Color::Rgb(210, 210, 210),
+        unmatched_bracket: Color::Rgb(200, 40, 40),
+        diff_added: Color::Rgb(210, 240, 215),
+        diff_removed: Color::Rgb(250, 215, 215),
+        diff_hunk: Color::Rgb(0, 120, 150),
+    };
+    /// Ratatui's own styling, which the editor started out with: the
+    /// terminal's colors, with the selection and caret in reverse video.
+    pub const PLAIN: Self = Self {
+        background: Color::Reset,
+        foreground: Color::Reset,
+        selection: Color::Reset,
+        selection_foreground: None,
+        caret: Color::Reset,
+        current_line: Color::Reset,
+        gutter: Color::Reset,
+        bold_selection: false,
+        reverse_video: true,
+        find_match: Color::Yellow,
+        find_match_foreground: Color::Black,
+        ruler: Color::DarkGray,
+        trailing_whitespace: Color::Red,
+        bracket_match: Color::DarkGray,
+        unmatched_bracket: Color::LightRed,
+        diff_added: Color::Green,
+        diff_removed: Color::Red,
+        diff_hunk: Color::Cyan,
+    };
+
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "plain" => Some(Self::PLAIN),
+            "dark" => Some(Self::DARK),
+            "light" => Some(Self::LIGHT),
+            "high_contrast" => Some(Self::HIGH_CONTRAST),
+            _ => None,
+        }
+    }
+
+    /// Starts from the `theme` preset, or the one matching the system color
+    /// scheme if there isn't one, and overrides individual colors from their
+    /// own parameters. Malformed values are ignored.
+    pub fn from_params(params: &UrlSearchParams, prefers_dark: bool) -> Self {
+        let mut theme = params
+            .get("theme")
+            .and_then(|name| Self::preset(&name))
+            .unwrap_or(if prefers_dark {
+                Self::DARK
+            } else {
+                Self::LIGHT
+            });
+
+        for (name, color) in [
+            ("bg", &mut theme.background),
+            ("fg", &mut theme.foreground),
+            ("selection", &mut theme.selection),
+            ("caret_color", &mut theme.caret),
+            ("current_line", &mut theme.current_line),
+            ("gutter", &mut theme.gutter),
+        ] {
+            if let Some(value) = params.get(name).and_then(|value| parse_color(&value)) {
+                *color = value;
+            }
+        }
+        if let Some(value) = params
+            .get("selection_fg")
+            .and_then(|value| parse_color(&value))
+        {
+            theme.selection_foreground = Some(value);
+        }
+
+        theme
+    }
+
+    pub fn base(&self) -> Style {
+        Style::new().fg(self.foreground).bg(self.background)
+    }
+}
+
+pub const DARK_COLOR_SCHEME_QUERY: &str = "(prefers-color-scheme: dark)";
+
+pub fn prefers_dark_color_scheme() -> bool {
+    web_sys::window()
+        .and_then(|win| win.match_media(DARK_COLOR_SCHEME_QUERY).ok().flatten())
+        .is_none_or(|list| list.matches())
+}
+
+/// Parses a color name or hex code. A `#` is awkward in a URL, so hex codes may
+/// leave it off.
+pub fn parse_color(value: &str) -> Option<Color> {
+    value
+        .parse()
+        .ok()
+        .or_else(|| format!("#{value}").parse().ok())
+}
+
+/// The most padding that can be asked for on each side, which still leaves
+/// room for text in a small window.
+pub const MAX_PADDING: u16 = 8;
+
+/// The widest the gutter can be asked to be, which is more digits than any
+/// document has lines.
+pub const MAX_GUTTER_WIDTH: u16 = 10;
+
+/// Everything about the editor that can be tuned without a code change. It's
+/// read from the page's query string by [`Config::from_query`], and the
+/// defaults draw the editor as it first looked.
+pub struct Config {
+    pub canvas_id: String,
+    /// The page title, which is also the window title.
+    pub title: String,
+    /// The post-processing effect the editor starts with.
+    pub effect: Effect,
+    /// WGSL source for [`Effect::Custom`]: an `fs_main` fragment shader that
+    /// can read the bindings declared in `shaders/fullscreen.wgsl`.
+    pub custom_shader: Option<String>,
+    pub crt_settings: CrtSettings,
+    /// The brightness and contrast applied over whichever effect is drawn.
+    pub adjustment: Adjustment,
+    pub line_numbers: bool,
+    pub gutter_min_width: u16,
+    /// Shows the caret position, document size and save state along the
+    /// bottom. Messages are shown there even when it's off.
+    pub status_bar: bool,
+    /// Cells left empty inside the border, around the gutter and text.
+    pub padding: Margin,
+    pub tab_width: usize,
+    /// Treats East Asian ambiguous-width characters as wide.
+    pub ambiguous_wide: bool,
+    pub caret_style: CaretStyle,
+    pub border: BorderStyle,
+    pub line_spacing: LineSpacing,
+    /// Type the closing bracket or quote along with the opening one.
+    pub auto_close: bool,
+    /// Indent one level more after a line ending in an opening bracket.
+    pub brace_indent: bool,
+    /// What the comment toggle puts in front of lines.
+    pub comment_prefix: String,
+    /// What a new document starts with.
+    pub template: String,
+    pub wrap: bool,
+    /// A font to fetch and use in place of the bundled one.
+    pub font_url: Option<String>,
+    /// Fonts to fetch and try, in order, for glyphs the primary font and the
+    /// bundled emoji font lack, e.g. CJK coverage.
+    pub fallback_font_urls: Vec<String>,
+    /// Displays the document without allowing edits. Selecting and copying
+    /// still work.
+    pub read_only: bool,
+    /// Draws spaces and tabs as visible glyphs.
+    pub show_whitespace: bool,
+    /// Keeps the caret's row in the middle of the editor, scrolling the text
+    /// under it.
+    pub typewriter: bool,
+    /// Animates the view toward a new scroll position instead of jumping.
+    pub smooth_scroll: bool,
+    /// Highlights spaces and tabs at the end of lines.
+    pub flag_trailing_whitespace: bool,
+    /// The column a guide is drawn at to show the line length limit.
+    pub ruler_column: usize,
+    pub show_ruler: bool,
+    /// Shows an overview of the whole document along the right edge.
+    pub minimap: bool,
+    pub theme: Theme,
+    /// Replaces the theme with [`Theme::HIGH_CONTRAST`].
+    pub high_contrast: bool,
+    /// Keeps the post-processing effect in the high-contrast mode. Otherwise
+    /// the text is drawn plain, since scanlines and blur cost contrast.
+    pub high_contrast_effects: bool,
+    /// The language to highlight the document as, if any.
+    pub language: Option<Language>,
+    /// The line ending the document is saved with.
+    pub line_ending: LineEnding,
+    /// Whether the theme tracks the system color scheme, because the page
+    /// didn't ask for a preset.
+    pub follow_color_scheme: bool,
+    /// The most frames drawn per second, or `None` to draw on every animation
+    /// frame.
+    pub max_fps: Option<u32>,
+    /// Save the document to local storage, and to its file if it has one,
+    /// once typing pauses for `auto_save_ms`.
+    pub auto_save: bool,
+    pub auto_save_ms: f64,
+    /// Types out the sample text by itself, over and over, until the user
+    /// does anything. It's skipped when a document is stored, to keep it.
+    pub demo: bool,
+    /// How long the demo waits between graphemes.
+    pub demo_ms: f64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            canvas_id: "glcanvas".to_string(),
+            title: "Ratatui Wgpu Text Editor".to_string(),
+            effect: Effect::default(),
+            custom_shader: None,
+            crt_settings: CrtSettings::default(),
+            adjustment: Adjustment::default(),
+            line_numbers: false,
+            gutter_min_width: 3,
+            status_bar: false,
+            padding: Margin::new(0, 0),
+            tab_width: 4,
+            ambiguous_wide: false,
+            caret_style: CaretStyle::default(),
+            border: BorderStyle::default(),
+            line_spacing: LineSpacing::default(),
+            auto_close: false,
+            brace_indent: false,
+            comment_prefix: "//".to_string(),
+            template: String::new(),
+            wrap: false,
+            font_url: None,
+            fallback_font_urls: vec![],
+            read_only: false,
+            show_whitespace: false,
+            typewriter: false,
+            smooth_scroll: true,
+            flag_trailing_whitespace: false,
+            ruler_column: 80,
+            show_ruler: false,
+            minimap: false,
+            theme: Theme::PLAIN,
+            high_contrast: false,
+            high_contrast_effects: false,
+            follow_color_scheme: true,
+            language: None,
+            line_ending: LineEnding::default(),
+            max_fps: Some(60),
+            auto_save: true,
+            auto_save_ms: SAVE_DEBOUNCE_MS,
+            demo: false,
+            demo_ms: DEMO_TYPING_MS,
+        }
+    }
+}
+
+impl Config {
+    pub fn widths(&self) -> CellWidths {
+        CellWidths {
+            tab_width: self.tab_width,
+            ambiguous_wide: self.ambiguous_wide,
+        }
+    }
+
+    /// The high-contrast theme when that mode is on, and otherwise the one
+    /// `params` ask for.
+    pub fn theme_for(&self, params: &UrlSearchParams, prefers_dark: bool) -> Theme {
+        if self.high_contrast {
+            Theme::HIGH_CONTRAST
+        } else {
+            Theme::from_params(params, prefers_dark)
+        }
+    }
+
+    pub fn query_params() -> Option<UrlSearchParams> {
+        let search = web_sys::window()?.location().search().ok()?;
+        UrlSearchParams::new_with_str(&search).ok()
+    }
+
+    /// The page's query parameters, or none at all when they can't be read,
+    /// so that everything derived from them takes its default.
+    pub fn query_params_or_empty() -> UrlSearchParams {
+        Self::query_params().unwrap_or_else(|| UrlSearchParams::new().unwrap())
+    }
+
+    pub fn from_query() -> Self {
+        let mut config = Self::default();
+        // The last choices made from the editor, which the page can still
+        // override.
+        if let Some(wrap) = stored_flag(WRAP_STORAGE_KEY) {
+            config.wrap = wrap;
+        }
+        if let Some(high_contrast) = stored_flag(HIGH_CONTRAST_STORAGE_KEY) {
+            config.high_contrast = high_contrast;
+        }
+        if let Some(brightness) = stored_number(BRIGHTNESS_STORAGE_KEY) {
+            config.adjustment.brightness = brightness;
+        }
+        if let Some(contrast) = stored_number(CONTRAST_STORAGE_KEY) {
+            config.adjustment.contrast = contrast;
+        }
+        config.adjustment = config.adjustment.clamped();
+        config.apply_params(&Self::query_params_or_empty());
+
+        config
+    }
+
+    /// Overrides whatever `params` set, leaving everything else as it is.
+    pub fn apply_params(&mut self, params: &UrlSearchParams) {
+        if let Some(canvas_id) = params.get("canvas") {
+            self.canvas_id = canvas_id;
+        }
+        if let Some(title) = params.get("title").filter(|title| !title.is_empty()) {
+            self.title = title;
+        }
+        // `crt` predates the other effects and still turns the CRT on or off.
+        if let Some(crt) = params.get("crt") {
+            self.effect = if parse_flag(&crt) {
+                Effect::Crt
+            } else {
+                Effect::Plain
+            };
+        }
+        self.custom_shader = params.get("shader").filter(|source| !source.is_empty());
+        if self.custom_shader.is_some() {
+            self.effect = Effect::Custom;
+        }
+        if let Some(effect) = params.get("effect").and_then(|name| Effect::parse(&name)) {
+            self.effect = effect;
+        }
+        for (name, value) in [
+            ("crt_curvature", &mut self.crt_settings.curvature),
+            ("crt_scanlines", &mut self.crt_settings.scanlines),
+            ("crt_vignette", &mut self.crt_settings.vignette),
+            ("crt_aberration", &mut self.crt_settings.aberration),
+            ("brightness", &mut self.adjustment.brightness),
+            ("contrast", &mut self.adjustment.contrast),
+        ] {
+            if let Some(parsed) = params
+                .get(name)
+                .and_then(|param| param.parse::<f32>().ok())
+                .filter(|parsed| parsed.is_finite() && *parsed >= 0.0)
+            {
+                *value = parsed;
+            }
+        }
+        self.adjustment = self.adjustment.clamped();
+        let cells = |name| {
+            params
+                .get(name)
+                .and_then(|cells| cells.parse::<u16>().ok())
+                .map(|cells| cells.min(MAX_PADDING))
+        };
+        if let Some(padding) = cells("padding") {
+            self.padding = Margin::new(padding, padding);
+        }
+        if let Some(padding) = cells("padding_x") {
+            self.padding.horizontal = padding;
+        }
+        if let Some(padding) = cells("padding_y") {
+            self.padding.vertical = padding;
+        }
+        if let Some(line_numbers) = params.get("line_numbers") {
+            self.line_numbers = parse_flag(&line_numbers);
+        }
+        if let Some(gutter_min_width) = params
+            .get("gutter_width")
+            .and_then(|width| width.parse::<u16>().ok())
+        {
+            self.gutter_min_width = gutter_min_width.min(MAX_GUTTER_WIDTH);
+        }
+        if let Some(status_bar) = params.get("status") {
+            self.status_bar = parse_flag(&status_bar);
+        }
+        if let Some(tab_width) = params.get("tab_width").and_then(|width| width.parse().ok()) {
+            self.tab_width = usize::clamp(tab_width, 1, MAX_TAB_WIDTH);
+        }
+        if let Some(ambiguous_wide) = params.get("ambiguous_wide") {
+            self.ambiguous_wide = parse_flag(&ambiguous_wide);
+        }
+        if let Some(caret_style) = params
+            .get("caret")
+            .and_then(|name| CaretStyle::parse(&name))
+        {
+            self.caret_style = caret_style;
+        }
+        if let Some(border) = params
+            .get("border")
+            .and_then(|name| BorderStyle::parse(&name))
+        {
+            self.border = border;
+        }
+        if let Some(line_spacing) = params
+            .get("spacing")
+            .and_then(|name| LineSpacing::parse(&name))
+        {
+            self.line_spacing = line_spacing;
+        }
+        if let Some(auto_close) = params.get("autoclose") {
+            self.auto_close = parse_flag(&auto_close);
+        }
+        if let Some(brace_indent) = params.get("brace_indent") {
+            self.brace_indent = parse_flag(&brace_indent);
+        }
+        if let Some(comment_prefix) = params.get("comment").filter(|prefix| !prefix.is_empty()) {
+            self.comment_prefix = comment_prefix;
+        }
+        if let Some(template) = params.get("template") {
+            self.template = template;
+        }
+        if let Some(wrap) = params.get("wrap") {
+            self.wrap = parse_flag(&wrap);
+        }
+        if let Some(read_only) = params.get("readonly") {
+            self.read_only = parse_flag(&read_only);
+        }
+        if let Some(show_whitespace) = params.get("whitespace") {
+            self.show_whitespace = parse_flag(&show_whitespace);
+        }
+        if let Some(minimap) = params.get("minimap") {
+            self.minimap = parse_flag(&minimap);
+        }
+        if let Some(typewriter) = params.get("typewriter") {
+            self.typewriter = parse_flag(&typewriter);
+        }
+        if let Some(smooth_scroll) = params.get("smooth") {
+            self.smooth_scroll = parse_flag(&smooth_scroll);
+        }
+        if let Some(flag_trailing_whitespace) = params.get("trailing") {
+            self.flag_trailing_whitespace = parse_flag(&flag_trailing_whitespace);
+        }
+        if let Some(high_contrast) = params.get("high_contrast") {
+            self.high_contrast = parse_flag(&high_contrast);
+        }
+        if let Some(high_contrast_effects) = params.get("high_contrast_effects") {
+            self.high_contrast_effects = parse_flag(&high_contrast_effects);
+        }
+        self.theme = self.theme_for(params, prefers_dark_color_scheme());
+        self.follow_color_scheme = params
+            .get("theme")
+            .and_then(|name| Theme::preset(&name))
+            .is_none();
+        self.language = params.get("syntax").and_then(|name| Language::parse(&name));
+        if let Some(ruler) = params.get("ruler") {
+            match ruler.parse() {
+                Ok(0) => self.show_ruler = false,
+                Ok(column) => {
+                    self.ruler_column = column;
+                    self.show_ruler = true;
+                }
+                Err(_) => self.show_ruler = parse_flag(&ruler),
+            }
+        }
+        if let Some(fps) = params.get("fps").and_then(|fps| fps.parse().ok()) {
+            self.max_fps = (fps > 0).then_some(fps);
+        }
+        if let Some(auto_save) = params.get("autosave") {
+            self.auto_save = parse_flag(&auto_save);
+        }
+        if let Some(auto_save_ms) = params
+            .get("autosave_ms")
+            .and_then(|ms| ms.parse::<f64>().ok())
+            .filter(|ms| ms.is_finite() && *ms >= 0.0)
+        {
+            self.auto_save_ms = auto_save_ms;
+        }
+        if let Some(demo) = params.get("demo") {
+            self.demo = parse_flag(&demo);
+        }
+        if let Some(demo_ms) = params
+            .get("demo_ms")
+            .and_then(|ms| ms.parse::<f64>().ok())
+            .filter(|ms| ms.is_finite() && *ms >= 0.0)
+        {
+            self.demo_ms = demo_ms;
+        }
+        self.font_url = params.get("font").filter(|url| !url.is_empty());
+        self.fallback_font_urls = params
+            .get_all("fallback")
+            .iter()
+            .filter_map(|url| url.as_string())
+            .filter(|url| !url.is_empty())
+            .collect();
+    }
+}
+
+/// Any value but "0" or "false" turns a flag on, so `?wrap` alone enables it.
+pub fn parse_flag(value: &str) -> bool {
+    value != "0" && value != "false"
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+
+    #[wasm_bindgen_test]
+    fn flags_are_on_unless_turned_off() {
+        for value in ["", "1", "true", "yes"] {
+            assert!(parse_flag(value), "{value:?}");
+        }
+        for value in ["0", "false"] {
+            assert!(!parse_flag(value), "{value:?}");
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn colors_parse_with_or_without_a_hash() {
+        assert_eq!(parse_color("#ff8000"), Some(Color::Rgb(255, 128, 0)));
+        // A "#" has to be escaped in a query string, so it can be left off.
+        assert_eq!(parse_color("ff8000"), Some(Color::Rgb(255, 128, 0)));
+        assert_eq!(parse_color("red"), Some(Color::Red));
+        assert_eq!(parse_color("not a color"), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn names_parse_back_to_the_same_setting() {
+        let mut spacing = LineSpacing::default();
+        for _ in 0..3 {
+            assert_eq!(LineSpacing::parse(spacing.name()), Some(spacing));
+            spacing = spacing.next();
+        }
+        assert_eq!(spacing, LineSpacing::default());
+
+        assert_eq!(CaretStyle::parse("bar"), Some(CaretStyle::Bar));
+        assert_eq!(BorderStyle::parse("none"), Some(BorderStyle::None));
+        assert_eq!(BorderStyle::None.set(), None);
+        assert_eq!(Theme::preset("plain"), Some(Theme::PLAIN));
+        assert_eq!(Theme::preset("light"), Some(Theme::LIGHT));
+        assert_eq!(Theme::preset("high_contrast"), Some(Theme::HIGH_CONTRAST));
+        assert_eq!(Theme::preset("solarized"), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn line_numbers_are_set_from_the_query() {
+        let mut config = Config::default();
+        let params = UrlSearchParams::new_with_str("line_numbers=0&gutter_width=5").unwrap();
+        config.apply_params(&params);
+        assert!(!config.line_numbers);
+        assert_eq!(config.gutter_min_width, 5);
+
+        let params = UrlSearchParams::new_with_str("line_numbers&gutter_width=500").unwrap();
+        config.apply_params(&params);
+        assert!(config.line_numbers);
+        assert_eq!(config.gutter_min_width, MAX_GUTTER_WIDTH);
+    }
+
+    #[wasm_bindgen_test]
+    fn widths_come_from_the_config() {
+        let config = Config {
+            tab_width: 2,
+            ambiguous_wide: true,
+            ..Config::default()
+        };
+        assert_eq!(
+            config.widths(),
+            CellWidths {
+                tab_width: 2,
+                ambiguous_wide: true,
+            }
+        );
+    }
+}
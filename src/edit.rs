@@ -0,0 +1,935 @@
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    ops::Range,
+};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Widens a byte range to cover the whole lines it touches. A range ending
+/// at the start of a line leaves that line out, so selecting lines by
+/// dragging down to the next one doesn't take it too.
+pub fn line_span(text: &str, bytes: Range<usize>) -> Range<usize> {
+    let start = text[..bytes.start].rfind('\n').map_or(0, |idx| idx + 1);
+    let mut end = bytes.end;
+    if end > start && text[..end].ends_with('\n') {
+        end -= 1;
+    }
+    let end = text[end..].find('\n').map_or(text.len(), |idx| end + idx);
+    start..end
+}
+
+/// Applies `transform` to the lines of `text`, keeping a trailing newline out
+/// of it so it stays at the end.
+fn map_lines(text: &str, transform: impl FnOnce(Vec<&str>) -> Vec<&str>) -> String {
+    let (body, newline) = match text.strip_suffix('\n') {
+        Some(body) => (body, "\n"),
+        None => (text, ""),
+    };
+    let mut lines = transform(body.split('\n').collect()).join("\n");
+    lines.push_str(newline);
+    lines
+}
+
+/// Sorts lines alphabetically. Equal lines keep their order.
+pub fn sort_lines(text: &str) -> String {
+    map_lines(text, |mut lines| {
+        lines.sort();
+        lines
+    })
+}
+
+/// Drops every line that repeats an earlier one.
+pub fn unique_lines(text: &str) -> String {
+    map_lines(text, |lines| {
+        let mut seen = HashSet::new();
+        lines
+            .into_iter()
+            .filter(|line| seen.insert(*line))
+            .collect()
+    })
+}
+
+/// Capitalizes the first letter of each word and lowercases the rest. A
+/// letter that capitalizes to several, like "ß" or "ﬁ", only has the first of
+/// them capitalized, so "ﬁsh" becomes "Fish" rather than "FIsh".
+pub fn title_case(text: &str) -> String {
+    text.split_word_bounds()
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => {
+                    let mut upper = first.to_uppercase();
+                    upper
+                        .next()
+                        .into_iter()
+                        .chain(upper.flat_map(char::to_lowercase))
+                        .chain(chars.flat_map(char::to_lowercase))
+                        .collect()
+                }
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// How a document's lines end. Textareas normalize their value to LF, so this
+/// is detected before a document goes in and applied when it comes out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+    /// Both kinds, which is saved as LF since the textarea can't keep track of
+    /// which line had which.
+    Mixed,
+}
+
+impl LineEnding {
+    pub fn detect(text: &str) -> Self {
+        let crlf = text.matches("\r\n").count();
+        let lf = text.matches('\n').count() - crlf;
+        match (crlf, lf) {
+            (0, _) => Self::Lf,
+            (_, 0) => Self::Crlf,
+            _ => Self::Mixed,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Lf => "LF",
+            Self::Crlf => "CRLF",
+            Self::Mixed => "Mixed",
+        }
+    }
+
+    /// Converts LF-only `text` to this line ending.
+    pub fn apply(self, text: &str) -> Cow<'_, str> {
+        match self {
+            Self::Crlf => text.replace('\n', "\r\n").into(),
+            Self::Lf | Self::Mixed => text.into(),
+        }
+    }
+}
+
+/// Parses the `bytes` of `document` as JSON and writes them back out,
+/// indented by two spaces when `pretty` or with no whitespace at all
+/// otherwise. Keys keep their order. Invalid JSON fails with a message saying
+/// where in the document the error is.
+pub fn reformat_json(document: &str, bytes: Range<usize>, pretty: bool) -> Result<String, String> {
+    let text = &document[bytes.clone()];
+    let value = serde_json::from_str::<serde_json::Value>(text).map_err(|err| {
+        let at = bytes.start + json_error_byte(text, &err);
+        let line_start = document[..at].rfind('\n').map_or(0, |idx| idx + 1);
+        let line = document[..at].matches('\n').count() + 1;
+        let column = document[line_start..at].chars().count() + 1;
+        // The message ends with the position in the JSON alone, which is
+        // replaced by the one in the document.
+        let message = err.to_string();
+        let position = format!(" at line {} column {}", err.line(), err.column());
+        let message = message.strip_suffix(&position).unwrap_or(&message);
+        format!("Invalid JSON at Ln {line}, Col {column}: {message}")
+    })?;
+    let formatted = if pretty {
+        serde_json::to_string_pretty(&value)
+    } else {
+        serde_json::to_string(&value)
+    };
+    let mut formatted = formatted.map_err(|err| err.to_string())?;
+    // Whatever followed the value, usually a final newline, is left as it was.
+    formatted.push_str(&text[text.trim_end().len()..]);
+    Ok(formatted)
+}
+
+/// The byte in `text` that serde_json reports `err` at. Its lines count from
+/// one, and its columns count bytes from one.
+fn json_error_byte(text: &str, err: &serde_json::Error) -> usize {
+    let line_start = text
+        .split_inclusive('\n')
+        .take(err.line().saturating_sub(1))
+        .map(str::len)
+        .sum::<usize>();
+    let mut at = (line_start + err.column().saturating_sub(1)).min(text.len());
+    while !text.is_char_boundary(at) {
+        at -= 1;
+    }
+    at
+}
+
+const BRACKETS: [(u8, u8); 3] = [(b'(', b')'), (b'[', b']'), (b'{', b'}')];
+
+/// A bracket beside the caret and the partner that closes or opens it, as
+/// byte offsets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BracketMatch {
+    pub bracket: usize,
+    /// Missing when the bracket is unbalanced.
+    pub partner: Option<usize>,
+}
+
+/// Finds the bracket just after the caret, or failing that the one just
+/// before it, and scans from it for its partner. Only brackets of the same
+/// kind nest, so `(]` still matches across a stray `]`.
+pub fn match_bracket(text: &str, caret: usize) -> Option<BracketMatch> {
+    let bytes = text.as_bytes();
+    let pair = |idx: usize| {
+        let byte = *bytes.get(idx)?;
+        BRACKETS
+            .into_iter()
+            .find(|&(open, close)| byte == open || byte == close)
+    };
+    let (bracket, (open, close)) = match pair(caret) {
+        Some(pair) => (caret, pair),
+        None => {
+            let before = caret.checked_sub(1)?;
+            (before, pair(before)?)
+        }
+    };
+
+    // Brackets are ASCII and never part of a multibyte character, so scanning
+    // bytes is safe.
+    let partner = if bytes[bracket] == open {
+        let after = bytes[bracket + 1..]
+            .iter()
+            .enumerate()
+            .map(|(idx, byte)| (bracket + 1 + idx, *byte));
+        find_partner(after, open, close)
+    } else {
+        let before = bytes[..bracket].iter().copied().enumerate().rev();
+        find_partner(before, close, open)
+    };
+    Some(BracketMatch { bracket, partner })
+}
+
+/// Returns the first `partner` that isn't balanced by a `nested` before it.
+fn find_partner(
+    bytes: impl Iterator<Item = (usize, u8)>,
+    nested: u8,
+    partner: u8,
+) -> Option<usize> {
+    let mut depth = 0usize;
+    for (idx, byte) in bytes {
+        if byte == nested {
+            depth += 1;
+        } else if byte == partner {
+            if depth == 0 {
+                return Some(idx);
+            }
+            depth -= 1;
+        }
+    }
+    None
+}
+
+/// The characters typed in pairs when auto-close is on, opener first. Quotes
+/// close themselves.
+pub const PAIRS: [(char, char); 6] = [
+    ('(', ')'),
+    ('[', ']'),
+    ('{', '}'),
+    ('"', '"'),
+    ('\'', '\''),
+    ('`', '`'),
+];
+
+/// A change to make in place of what was typed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Insertion {
+    /// The bytes of the document to replace.
+    pub replace: Range<usize>,
+    pub text: String,
+    /// What to select afterwards, in bytes into `text`.
+    pub select: Range<usize>,
+}
+
+/// Works out what typing `typed` over the `selection` should do with
+/// auto-close on, or `None` if it should be inserted as it is.
+///
+/// An opener wraps a selection, and is otherwise followed by its closer when
+/// only whitespace or a closing bracket comes after the caret. Typing a closer
+/// that is already next steps over it instead. A quote after a letter is taken
+/// as an apostrophe and left alone.
+pub fn auto_close(text: &str, selection: Range<usize>, typed: char) -> Option<Insertion> {
+    let open = PAIRS.into_iter().find(|&(open, _)| open == typed);
+    let close = PAIRS.into_iter().find(|&(_, close)| close == typed);
+
+    if !selection.is_empty() {
+        let (open, close) = open?;
+        let inner = &text[selection.clone()];
+        return Some(Insertion {
+            replace: selection.clone(),
+            text: format!("{open}{inner}{close}"),
+            select: open.len_utf8()..open.len_utf8() + inner.len(),
+        });
+    }
+
+    let caret = selection.start;
+    let next = text[caret..].chars().next();
+    if close.is_some() && next == Some(typed) {
+        let len = typed.len_utf8();
+        return Some(Insertion {
+            replace: caret..caret + len,
+            text: typed.to_string(),
+            select: len..len,
+        });
+    }
+
+    let (open, close) = open?;
+    let previous = text[..caret].chars().next_back();
+    if open == close && previous.is_some_and(char::is_alphanumeric) {
+        return None;
+    }
+    let closes_here = next.is_none_or(|next| {
+        next.is_whitespace()
+            || PAIRS
+                .iter()
+                .any(|&(open, close)| next == close && open != close)
+    });
+    closes_here.then(|| Insertion {
+        replace: caret..caret,
+        text: format!("{open}{close}"),
+        select: open.len_utf8()..open.len_utf8(),
+    })
+}
+
+/// What to type for a line break at `caret`, and the byte offset into it to
+/// leave the caret at. The new line starts with the current line's
+/// indentation. With a `brace_indent` width, a line ending in an opening
+/// bracket gets one level more, a tab if the line is indented with tabs, and
+/// the closing bracket right after the caret moves down to a line of its own.
+pub fn newline(text: &str, caret: usize, brace_indent: Option<usize>) -> (String, usize) {
+    let line_start = text[..caret].rfind('\n').map_or(0, |idx| idx + 1);
+    let before = &text[line_start..caret];
+    let indent = &before[..before.len() - before.trim_start_matches([' ', '\t']).len()];
+
+    let mut inserted = format!("\n{indent}");
+    let opener = before
+        .trim_end()
+        .bytes()
+        .next_back()
+        .and_then(|last| BRACKETS.into_iter().find(|&(open, _)| open == last));
+    if let (Some(width), Some((_, close))) = (brace_indent, opener) {
+        if indent.contains('\t') {
+            inserted.push('\t');
+        } else {
+            inserted.extend(std::iter::repeat_n(' ', width));
+        }
+        let inside = inserted.len();
+        if text[caret..].starts_with(close as char) {
+            inserted.push('\n');
+            inserted.push_str(indent);
+        }
+        return (inserted, inside);
+    }
+
+    let inside = inserted.len();
+    (inserted, inside)
+}
+
+/// Comments out each line after its indentation, or uncomments them all if
+/// every line already is. Blank lines are left alone either way.
+pub fn toggle_comment(text: &str, prefix: &str) -> String {
+    let split = |line: &'_ str| {
+        let body = line.trim_start_matches([' ', '\t']);
+        (&line[..line.len() - body.len()], body)
+    };
+    let mut lines = text
+        .split('\n')
+        .map(split)
+        .filter(|(_, body)| !body.is_empty());
+    if lines.clone().next().is_none() {
+        return text.to_string();
+    }
+    let commented = lines.all(|(_, body)| body.starts_with(prefix));
+
+    text.split('\n')
+        .map(|line| {
+            let (indent, body) = split(line);
+            if body.is_empty() {
+                line.to_string()
+            } else if commented {
+                let body = &body[prefix.len()..];
+                format!("{indent}{}", body.strip_prefix(' ').unwrap_or(body))
+            } else {
+                format!("{indent}{prefix} {body}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Swaps the whole lines in `block` with the line above or below them. The
+/// insertion selects where the lines end up. Returns `None` at either end of
+/// the document.
+pub fn move_lines(text: &str, block: Range<usize>, up: bool) -> Option<Insertion> {
+    let lines = &text[block.clone()];
+    if up {
+        let above = line_span(text, block.start.checked_sub(1)?..block.start - 1);
+        let neighbor = &text[above.clone()];
+        Some(Insertion {
+            replace: above.start..block.end,
+            text: format!("{lines}\n{neighbor}"),
+            select: 0..lines.len(),
+        })
+    } else {
+        if block.end == text.len() {
+            return None;
+        }
+        let below = line_span(text, block.end + 1..block.end + 1);
+        let neighbor = &text[below.clone()];
+        Some(Insertion {
+            replace: block.start..below.end,
+            text: format!("{neighbor}\n{lines}"),
+            select: neighbor.len() + 1..neighbor.len() + 1 + lines.len(),
+        })
+    }
+}
+
+/// Copies the whole lines in `block` to just below them, selecting the copy.
+pub fn duplicate_lines(text: &str, block: Range<usize>) -> Insertion {
+    let lines = &text[block.clone()];
+    Insertion {
+        replace: block.end..block.end,
+        text: format!("\n{lines}"),
+        select: 1..1 + lines.len(),
+    }
+}
+
+/// The text an edit from `old` to `new` put in: whatever of `new` is left
+/// between what the two start and end with.
+pub fn inserted<'a>(old: &str, new: &'a str) -> &'a str {
+    let mut start = old
+        .bytes()
+        .zip(new.bytes())
+        .take_while(|(old, new)| old == new)
+        .count();
+    while !new.is_char_boundary(start) {
+        start -= 1;
+    }
+    let same_end = old
+        .bytes()
+        .rev()
+        .zip(new.bytes().rev())
+        .take(old.len().min(new.len()) - start)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let mut end = new.len() - same_end;
+    while !new.is_char_boundary(end) {
+        end += 1;
+    }
+    &new[start..end]
+}
+
+const BASE64_DIGITS: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes the UTF-8 bytes of `text` as padded base64.
+pub fn base64_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len().div_ceil(3) * 4);
+    for chunk in text.as_bytes().chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (idx, &byte)| {
+            bits | (byte as u32) << (16 - 8 * idx)
+        });
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                encoded.push(BASE64_DIGITS[(bits >> (18 - 6 * idx)) as usize & 63] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Decodes base64 back to text. Whitespace is skipped and padding is
+/// optional, and the URL-safe `-` and `_` digits are accepted too. Fails if
+/// the input isn't base64 or doesn't decode to UTF-8.
+pub fn base64_decode(text: &str) -> Result<String, String> {
+    let digits = text.trim_end().trim_end_matches('=');
+    let mut bytes = Vec::with_capacity(digits.len() / 4 * 3);
+    let mut bits = 0u32;
+    let mut held = 0;
+    let mut count = 0;
+    for digit in digits.chars().filter(|c| !c.is_whitespace()) {
+        let value = match digit {
+            '-' => 62,
+            '_' => 63,
+            _ => BASE64_DIGITS
+                .iter()
+                .position(|&known| digit.is_ascii() && known == digit as u8)
+                .ok_or_else(|| format!("'{digit}' isn't a base64 digit"))? as u32,
+        };
+        bits = bits << 6 | value;
+        held += 6;
+        count += 1;
+        if held >= 8 {
+            held -= 8;
+            bytes.push((bits >> held) as u8);
+            bits &= (1 << held) - 1;
+        }
+    }
+    if count % 4 == 1 {
+        return Err("the base64 is truncated".to_string());
+    }
+    String::from_utf8(bytes).map_err(|_| "it doesn't decode to UTF-8 text".to_string())
+}
+
+/// Percent-encodes everything but the characters `encodeURIComponent` leaves
+/// alone.
+pub fn url_encode(text: &str) -> String {
+    js_sys::encode_uri_component(text).into()
+}
+
+/// Reverses percent-encoding. Fails on a malformed escape, or one that isn't
+/// UTF-8.
+pub fn url_decode(text: &str) -> Result<String, String> {
+    js_sys::decode_uri_component(text)
+        .map(String::from)
+        .map_err(|_| "it has a malformed percent escape".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+    use crate::input::byte_at;
+
+    /// The document once `insertion` is made, and the bytes of it left
+    /// selected.
+    fn apply(text: &str, insertion: Insertion) -> (String, Range<usize>) {
+        let start = insertion.replace.start;
+        let mut applied = text.to_string();
+        applied.replace_range(insertion.replace, &insertion.text);
+        (
+            applied,
+            start + insertion.select.start..start + insertion.select.end,
+        )
+    }
+
+    /// What typing `typed` over `selection` leaves with auto-close on.
+    fn auto_closed(
+        text: &str,
+        selection: Range<usize>,
+        typed: char,
+    ) -> Option<(String, Range<usize>)> {
+        auto_close(text, selection, typed).map(|insertion| apply(text, insertion))
+    }
+
+    #[wasm_bindgen_test]
+    fn line_commands_keep_a_trailing_newline_at_the_end() {
+        assert_eq!(sort_lines("b\na\nc\n"), "a\nb\nc\n");
+        assert_eq!(sort_lines("b\na\nc"), "a\nb\nc");
+        assert_eq!(unique_lines("a\nb\na\n"), "a\nb\n");
+        // Only the last newline is kept out, so a blank line still sorts first.
+        assert_eq!(sort_lines("b\n\na\n"), "\na\nb\n");
+    }
+
+    #[wasm_bindgen_test]
+    fn line_span_widens_to_whole_lines() {
+        let text = "one\ntwo\nthree";
+        // A caret takes the whole line it is on.
+        assert_eq!(&text[line_span(text, 5..5)], "two");
+        assert_eq!(&text[line_span(text, 0..0)], "one");
+        assert_eq!(&text[line_span(text, 13..13)], "three");
+        assert_eq!(&text[line_span(text, 1..6)], "one\ntwo");
+        // Ending at the start of a line leaves that line out.
+        assert_eq!(&text[line_span(text, 1..8)], "one\ntwo");
+        assert_eq!(&text[line_span(text, 4..4)], "two");
+    }
+
+    #[wasm_bindgen_test]
+    fn line_commands_keep_crlf_endings() {
+        assert_eq!(sort_lines("b\r\na\r\nc\r\n"), "a\r\nb\r\nc\r\n");
+        assert_eq!(unique_lines("a\r\nb\r\na\r\n"), "a\r\nb\r\n");
+    }
+
+    /// The text and UTF-16 selection the textarea ends up with once the
+    /// `selection` is replaced by `transform` of it and the result selected.
+    fn transform_selected(
+        text: &str,
+        selection: Range<u32>,
+        transform: fn(&str) -> String,
+    ) -> (String, Range<u32>) {
+        let bytes = byte_at(text, selection.start)..byte_at(text, selection.end);
+        let transformed = transform(&text[bytes.clone()]);
+        let end = selection.start + transformed.encode_utf16().count() as u32;
+        (
+            format!(
+                "{}{transformed}{}",
+                &text[..bytes.start],
+                &text[bytes.end..]
+            ),
+            selection.start..end,
+        )
+    }
+
+    #[wasm_bindgen_test]
+    fn case_changes_that_grow_the_text_select_all_of_it() {
+        assert_eq!(
+            transform_selected("die straße hier", 4..10, str::to_uppercase),
+            ("die STRASSE hier".to_string(), 4..11)
+        );
+        assert_eq!(
+            transform_selected("a ﬁne day", 2..5, str::to_uppercase),
+            ("a FINE day".to_string(), 2..6)
+        );
+        // Lowercasing "İ" adds a combining dot.
+        assert_eq!(
+            transform_selected("İ😊x", 0..3, str::to_lowercase),
+            ("i\u{307}😊x".to_string(), 0..4)
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn title_case_capitalizes_only_the_first_letter_of_each_word() {
+        assert_eq!(title_case("hello WORLD"), "Hello World");
+        assert_eq!(title_case("don't stop"), "Don't Stop");
+        assert_eq!(title_case("ﬁsh and ßtraße"), "Fish And Sstraße");
+        assert_eq!(
+            transform_selected("😊 ﬁsh", 3..4, title_case),
+            ("😊 Fish".to_string(), 3..5)
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn line_endings_are_detected_from_every_line_break() {
+        assert_eq!(LineEnding::detect(""), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("a\nb\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("a\r\nb\r\n"), LineEnding::Crlf);
+        assert_eq!(LineEnding::detect("a\r\nb\nc"), LineEnding::Mixed);
+        // A lone carriage return doesn't end a line.
+        assert_eq!(LineEnding::detect("a\rb\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("a\rb\r\n"), LineEnding::Crlf);
+    }
+
+    #[wasm_bindgen_test]
+    fn line_endings_survive_the_textarea() {
+        for text in ["a\nb\n", "a\r\nb\r\n", "a\r\n\r\nb"] {
+            let ending = LineEnding::detect(text);
+            // The textarea normalizes its value to LF.
+            let normalized = text.replace("\r\n", "\n");
+            assert_eq!(ending.apply(&normalized), text);
+        }
+        // Mixed endings can't be restored, so they are saved as LF.
+        assert_eq!(LineEnding::Mixed.apply("a\nb\n"), "a\nb\n");
+    }
+
+    #[wasm_bindgen_test]
+    fn json_is_prettified_and_minified_in_key_order() {
+        let minified = r#"{"b":1,"a":[true,null],"c":{}}"#;
+        let pretty = "{\n  \"b\": 1,\n  \"a\": [\n    true,\n    null\n  ],\n  \"c\": {}\n}";
+        let whole = |text: &str| 0..text.len();
+        assert_eq!(
+            reformat_json(minified, whole(minified), true).as_deref(),
+            Ok(pretty)
+        );
+        assert_eq!(
+            reformat_json(pretty, whole(pretty), false).as_deref(),
+            Ok(minified)
+        );
+        // A final newline is kept.
+        let text = format!("{minified}\n");
+        assert_eq!(
+            reformat_json(&text, whole(&text), true),
+            Ok(format!("{pretty}\n"))
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn json_errors_are_placed_in_the_document() {
+        // The JSON starts on the second line of the document, and the colon is
+        // missing from its second line.
+        let document = "notes\n{\n  \"a\" 1\n}\n";
+        assert_eq!(
+            reformat_json(document, 6..document.len(), true),
+            Err("Invalid JSON at Ln 3, Col 7: expected `:`".to_string())
+        );
+        // Columns count characters, not bytes.
+        let document = r#"["é", x]"#;
+        assert_eq!(
+            reformat_json(document, 0..document.len(), false),
+            Err("Invalid JSON at Ln 1, Col 7: expected value".to_string())
+        );
+        // A selection starting partway through a line shifts the column.
+        let document = r#"let x = {"a": ]"#;
+        assert_eq!(
+            reformat_json(document, 8..document.len(), false),
+            Err("Invalid JSON at Ln 1, Col 15: expected value".to_string())
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn brackets_match_across_nested_pairs() {
+        let text = "f(a[b](c)é)";
+        let matched = |caret| match_bracket(text, caret);
+        let found = |bracket, partner| {
+            Some(BracketMatch {
+                bracket,
+                partner: Some(partner),
+            })
+        };
+        assert_eq!(matched(1), found(1, 11));
+        // The bracket after the caret wins over the one before it.
+        assert_eq!(matched(3), found(3, 5));
+        assert_eq!(matched(6), found(6, 8));
+        assert_eq!(matched(12), found(11, 1));
+        // Only brackets of the same kind nest.
+        assert_eq!(match_bracket("([)]", 0), found(0, 2));
+    }
+
+    #[wasm_bindgen_test]
+    fn unbalanced_brackets_have_no_partner() {
+        let unmatched = |bracket| {
+            Some(BracketMatch {
+                bracket,
+                partner: None,
+            })
+        };
+        assert_eq!(match_bracket("((a)", 0), unmatched(0));
+        assert_eq!(match_bracket("a)", 2), unmatched(1));
+        assert_eq!(match_bracket("(]", 0), unmatched(0));
+        assert_eq!(match_bracket("abc", 1), None);
+        assert_eq!(match_bracket("", 0), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn brackets_beside_multibyte_characters_match() {
+        let text = "(é)😊[";
+        // Between "é" and ")", and between ")" and the emoji.
+        assert_eq!(
+            match_bracket(text, 3),
+            Some(BracketMatch {
+                bracket: 3,
+                partner: Some(0),
+            })
+        );
+        assert_eq!(
+            match_bracket(text, 4),
+            Some(BracketMatch {
+                bracket: 3,
+                partner: Some(0),
+            })
+        );
+        // Just after the "é", with no bracket on either side.
+        assert_eq!(match_bracket("é", 2), None);
+        assert_eq!(
+            match_bracket(text, 8),
+            Some(BracketMatch {
+                bracket: 8,
+                partner: None,
+            })
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn openers_are_closed_before_whitespace_and_closers() {
+        assert_eq!(auto_closed("", 0..0, '('), Some(("()".to_string(), 1..1)));
+        assert_eq!(
+            auto_closed("f x", 1..1, '{'),
+            Some(("f{} x".to_string(), 2..2))
+        );
+        assert_eq!(
+            auto_closed("()", 1..1, '['),
+            Some(("([])".to_string(), 2..2))
+        );
+        // Right before a word the opener is probably meant for it.
+        assert_eq!(auto_closed("x", 0..0, '('), None);
+        // Other characters are typed as they are.
+        assert_eq!(auto_closed("", 0..0, 'x'), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn typing_a_closer_steps_over_the_one_already_there() {
+        assert_eq!(auto_closed("()", 1..1, ')'), Some(("()".to_string(), 2..2)));
+        assert_eq!(
+            auto_closed("\"a\"", 2..2, '"'),
+            Some(("\"a\"".to_string(), 3..3))
+        );
+        // A different closer is typed as it is.
+        assert_eq!(auto_closed("()", 1..1, ']'), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn quotes_after_a_letter_are_apostrophes() {
+        assert_eq!(auto_closed("don", 3..3, '\''), None);
+        assert_eq!(auto_closed("café", 5..5, '\''), None);
+        assert_eq!(
+            auto_closed("x = ", 4..4, '"'),
+            Some(("x = \"\"".to_string(), 5..5))
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn openers_wrap_the_selection() {
+        assert_eq!(
+            auto_closed("say hi", 4..6, '"'),
+            Some(("say \"hi\"".to_string(), 5..7))
+        );
+        assert_eq!(auto_closed("é", 0..2, '('), Some(("(é)".to_string(), 1..3)));
+        assert_eq!(auto_closed("say hi", 4..6, 'x'), None);
+        // A closer with a selection replaces it like any other character.
+        assert_eq!(auto_closed("say hi", 4..6, ')'), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn newlines_carry_the_indentation_over() {
+        assert_eq!(newline("    foo", 7, None), ("\n    ".to_string(), 5));
+        assert_eq!(newline("\tfoo", 4, None), ("\n\t".to_string(), 2));
+        assert_eq!(newline(" \tfoo\nbar", 5, None), ("\n \t".to_string(), 3));
+        // Only the line the caret is on counts.
+        assert_eq!(newline("    foo\nbar", 11, None), ("\n".to_string(), 1));
+        // Without brace indenting an opener is like any other character.
+        assert_eq!(newline("{", 1, None), ("\n".to_string(), 1));
+    }
+
+    #[wasm_bindgen_test]
+    fn newlines_in_the_indentation_keep_what_is_before_the_caret() {
+        // The rest of the indentation moves down with the text after it.
+        assert_eq!(newline("    foo", 2, None), ("\n  ".to_string(), 3));
+        assert_eq!(newline("\t\tfoo", 1, Some(4)), ("\n\t".to_string(), 2));
+    }
+
+    #[wasm_bindgen_test]
+    fn newlines_after_an_opener_indent_one_level_more() {
+        assert_eq!(newline("  if x {", 8, Some(4)), ("\n      ".to_string(), 7));
+        // Tabs indent by a tab whatever the width.
+        assert_eq!(newline("\tif x {", 7, Some(4)), ("\n\t\t".to_string(), 3));
+        // Trailing whitespace after the opener doesn't matter.
+        assert_eq!(newline("[ ", 2, Some(2)), ("\n  ".to_string(), 3));
+        // A closer right after the caret moves down to a line of its own.
+        assert_eq!(
+            newline("  f()", 4, Some(4)),
+            ("\n      \n  ".to_string(), 7)
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn comments_are_toggled_on_every_line_together() {
+        assert_eq!(toggle_comment("a\nb", "//"), "// a\n// b");
+        assert_eq!(toggle_comment("// a\n//b", "//"), "a\nb");
+        // While any line is uncommented, commenting wins.
+        assert_eq!(toggle_comment("a\n// b", "//"), "// a\n// // b");
+        assert_eq!(toggle_comment("x", "#"), "# x");
+    }
+
+    #[wasm_bindgen_test]
+    fn blank_lines_are_not_commented() {
+        assert_eq!(toggle_comment("a\n\n  \nb", "//"), "// a\n\n  \n// b");
+        assert_eq!(toggle_comment("// a\n\n// b", "//"), "a\n\nb");
+        assert_eq!(toggle_comment("\n  ", "//"), "\n  ");
+    }
+
+    #[wasm_bindgen_test]
+    fn comments_go_after_each_lines_indentation() {
+        let block = "  if x {\n\t  y\n  }";
+        let commented = "  // if x {\n\t  // y\n  // }";
+        assert_eq!(toggle_comment(block, "//"), commented);
+        assert_eq!(toggle_comment(commented, "//"), block);
+    }
+
+    #[wasm_bindgen_test]
+    fn lines_move_past_their_neighbor_and_stay_selected() {
+        let text = "a\nb\nc";
+        let moved = |block, up| move_lines(text, block, up).map(|moved| apply(text, moved));
+        assert_eq!(moved(2..3, true), Some(("b\na\nc".to_string(), 0..1)));
+        // Onto the last line, which has no newline after it.
+        assert_eq!(moved(2..3, false), Some(("a\nc\nb".to_string(), 4..5)));
+        assert_eq!(moved(0..3, false), Some(("c\na\nb".to_string(), 2..5)));
+
+        let text = "é\nx\ny";
+        let (moved, selected) = apply(text, move_lines(text, 0..4, false).unwrap());
+        assert_eq!(moved, "y\né\nx");
+        assert_eq!(&moved[selected], "é\nx");
+    }
+
+    #[wasm_bindgen_test]
+    fn lines_at_either_end_stay_put() {
+        let text = "a\nb\nc";
+        assert_eq!(move_lines(text, 0..1, true), None);
+        assert_eq!(move_lines(text, 4..5, false), None);
+        assert_eq!(move_lines(text, 0..5, true), None);
+        assert_eq!(move_lines(text, 0..5, false), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn duplicated_lines_go_below_and_the_copy_is_selected() {
+        let duplicated = |text: &str, selection: Range<usize>| {
+            apply(text, duplicate_lines(text, line_span(text, selection)))
+        };
+        // A caret duplicates the line it is on.
+        assert_eq!(duplicated("a\nb", 0..0), ("a\na\nb".to_string(), 2..3));
+        assert_eq!(duplicated("a\n", 1..1), ("a\na\n".to_string(), 2..3));
+        assert_eq!(
+            duplicated("a\nb\nc", 0..3),
+            ("a\nb\na\nb\nc".to_string(), 4..7)
+        );
+        // The last line gets a newline before its copy.
+        assert_eq!(duplicated("a\nb", 3..3), ("a\nb\nb".to_string(), 4..5));
+    }
+
+    /// The test vectors from RFC 4648, section 10.
+    const BASE64_VECTORS: [(&str, &str); 7] = [
+        ("", ""),
+        ("f", "Zg=="),
+        ("fo", "Zm8="),
+        ("foo", "Zm9v"),
+        ("foob", "Zm9vYg=="),
+        ("fooba", "Zm9vYmE="),
+        ("foobar", "Zm9vYmFy"),
+    ];
+
+    #[wasm_bindgen_test]
+    fn base64_round_trips_the_rfc_vectors() {
+        for (text, encoded) in BASE64_VECTORS {
+            assert_eq!(base64_encode(text), encoded, "{text:?}");
+            assert_eq!(base64_decode(encoded).as_deref(), Ok(text), "{encoded:?}");
+            // Padding is optional.
+            let unpadded = encoded.trim_end_matches('=');
+            assert_eq!(base64_decode(unpadded).as_deref(), Ok(text), "{unpadded:?}");
+        }
+        assert_eq!(base64_encode("é😊"), "w6nwn5iK");
+        assert_eq!(base64_decode("w6nwn5iK").as_deref(), Ok("é😊"));
+    }
+
+    #[wasm_bindgen_test]
+    fn base64_decoding_accepts_whitespace_and_url_safe_digits() {
+        assert_eq!(base64_decode("Zm9v\nYmFy\n").as_deref(), Ok("foobar"));
+        assert_eq!(base64_decode("PDw/Pz4+").as_deref(), Ok("<<??>>"));
+        assert_eq!(base64_decode("PDw_Pz4-").as_deref(), Ok("<<??>>"));
+    }
+
+    #[wasm_bindgen_test]
+    fn base64_decoding_rejects_what_isnt_base64_text() {
+        assert_eq!(
+            base64_decode("Zm9v!"),
+            Err("'!' isn't a base64 digit".to_string())
+        );
+        assert_eq!(
+            base64_decode("Zg==Zg=="),
+            Err("'=' isn't a base64 digit".to_string())
+        );
+        assert_eq!(
+            base64_decode("Zm9vY"),
+            Err("the base64 is truncated".to_string())
+        );
+        // 0xFF on its own isn't UTF-8.
+        assert_eq!(
+            base64_decode("/w=="),
+            Err("it doesn't decode to UTF-8 text".to_string())
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn inserted_text_is_what_lies_between_the_unchanged_ends() {
+        assert_eq!(inserted("abc", "abXc"), "X");
+        assert_eq!(inserted("abc", "abc"), "");
+        assert_eq!(inserted("abc", "ac"), "");
+        assert_eq!(inserted("", "new"), "new");
+        // Repeated letters are only counted once between the two ends.
+        assert_eq!(inserted("aa", "aaa"), "a");
+        // "é" and "ê" share their first byte, which isn't split off.
+        assert_eq!(inserted("é", "ê"), "ê");
+    }
+}
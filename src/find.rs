@@ -0,0 +1,315 @@
+use std::ops::Range;
+
+use ratatui::prelude::*;
+use regex::RegexBuilder;
+
+/// The state of the find bar. Matches are UTF-16 ranges into the document, in
+/// order, and are kept up to date while the bar is open.
+#[derive(Default)]
+pub struct FindBar {
+    pub open: bool,
+    pub query: String,
+    pub case_sensitive: bool,
+    pub regex: bool,
+    /// Why the query couldn't be compiled as a regex.
+    pub error: Option<String>,
+    pub matches: Vec<Range<u32>>,
+    pub replacing: bool,
+    pub replacement: String,
+    pub replace_focused: bool,
+}
+
+impl FindBar {
+    pub fn update(&mut self, text: &str) {
+        self.error = None;
+        self.matches = if !self.open {
+            vec![]
+        } else if self.regex {
+            match find_regex_matches(text, &self.query, self.case_sensitive) {
+                Ok(matches) => matches,
+                Err(err) => {
+                    self.error = Some(err);
+                    vec![]
+                }
+            }
+        } else {
+            find_matches(text, &self.query, self.case_sensitive)
+        };
+    }
+
+    /// The first match after the selection, or the last one before it, wrapping
+    /// around the ends of the document.
+    pub fn step(&self, selection: &Range<u32>, forward: bool) -> Option<Range<u32>> {
+        let found = if forward {
+            self.matches
+                .iter()
+                .find(|found| found.start >= selection.end)
+                .or(self.matches.first())
+        } else {
+            self.matches
+                .iter()
+                .rev()
+                .find(|found| found.end <= selection.start)
+                .or(self.matches.last())
+        };
+
+        found.cloned()
+    }
+
+    pub fn height(&self) -> u16 {
+        match (self.open, self.replacing) {
+            (false, _) => 0,
+            (true, false) => 1,
+            (true, true) => 2,
+        }
+    }
+
+    pub fn prompt(&self, selection: &Range<u32>) -> Vec<Line<'static>> {
+        let case = if self.case_sensitive {
+            "Match case"
+        } else {
+            "Ignore case"
+        };
+        let mode = if self.regex { "Regex" } else { "Text" };
+        let count = if let Some(err) = &self.error {
+            format!(" | Invalid pattern: {err}")
+        } else if self.query.is_empty() {
+            String::new()
+        } else if let Some(idx) = self.matches.iter().position(|found| found == selection) {
+            format!(" | {} of {}", idx + 1, self.matches.len())
+        } else {
+            format!(" | {} matches", self.matches.len())
+        };
+
+        let caret = |focused: bool| if focused { "▏" } else { "" };
+
+        let mut lines = vec![Line::from(format!(
+            " Find:    {}{} | {case} (Alt+C) | {mode} (Alt+R){count}",
+            self.query,
+            caret(!self.replace_focused)
+        ))];
+        if self.replacing {
+            lines.push(Line::from(format!(
+                " Replace: {}{} | Enter: replace, Ctrl+Enter: replace all",
+                self.replacement,
+                caret(self.replace_focused)
+            )));
+        }
+
+        lines
+    }
+}
+
+/// Finds the non-overlapping occurrences of `query`, as UTF-16 ranges.
+pub fn find_matches(text: &str, query: &str, case_sensitive: bool) -> Vec<Range<u32>> {
+    if query.is_empty() {
+        return vec![];
+    }
+
+    // Returns the length in bytes of the match at the start of `rest`, if there is
+    // one. Characters are compared one at a time so case folding can't change
+    // how much of the text a match covers.
+    let match_len = |rest: &str| {
+        let mut haystack = rest.chars();
+        let mut len = 0;
+        for expected in query.chars() {
+            let c = haystack.next()?;
+            let same =
+                c == expected || (!case_sensitive && c.to_lowercase().eq(expected.to_lowercase()));
+            if !same {
+                return None;
+            }
+            len += c.len_utf8();
+        }
+        Some(len)
+    };
+
+    let mut matches = vec![];
+    let mut offset = 0;
+    let mut resume_at = 0;
+    for (idx, c) in text.char_indices() {
+        if idx >= resume_at
+            && let Some(len) = match_len(&text[idx..])
+        {
+            let units = text[idx..idx + len].encode_utf16().count() as u32;
+            matches.push(offset..offset + units);
+            resume_at = idx + len;
+        }
+        offset += c.len_utf16() as u32;
+    }
+
+    matches
+}
+
+/// Replaces each of `ranges`, which are UTF-16 and in order, with
+/// `replacement`. Returns the new text along with where `caret` ends up in it.
+/// A caret inside a replaced range moves to the end of its replacement.
+pub fn replace_ranges(
+    text: &str,
+    ranges: &[Range<u32>],
+    replacement: &str,
+    caret: u32,
+) -> (String, u32) {
+    let units = replacement.encode_utf16().count() as i64;
+
+    // Ranges are converted to byte offsets in a single pass over the text.
+    let mut chars = text.char_indices();
+    let mut byte = 0;
+    let mut unit = 0;
+    let mut to_byte = |target: u32| {
+        while unit < target {
+            let Some((idx, c)) = chars.next() else {
+                break;
+            };
+            byte = idx + c.len_utf8();
+            unit += c.len_utf16() as u32;
+        }
+        byte
+    };
+
+    let mut replaced = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut delta = 0;
+    let mut new_caret = None;
+    for range in ranges {
+        let start = to_byte(range.start);
+        let end = to_byte(range.end);
+        replaced.push_str(&text[copied..start]);
+        replaced.push_str(replacement);
+        copied = end;
+
+        if new_caret.is_none() && range.start < caret && caret < range.end {
+            new_caret = Some(range.start as i64 + delta + units);
+        }
+        if range.end <= caret {
+            delta += units - range.len() as i64;
+        }
+    }
+    replaced.push_str(&text[copied..]);
+
+    let new_caret = new_caret.unwrap_or(caret as i64 + delta);
+    (replaced, new_caret as u32)
+}
+
+/// Finds the matches of the regex `pattern`, as UTF-16 ranges. `^` and `$`
+/// match at line breaks, and empty matches are skipped since there's nothing to
+/// highlight. Returns a short description of the problem if the pattern is
+/// invalid.
+pub fn find_regex_matches(
+    text: &str,
+    pattern: &str,
+    case_sensitive: bool,
+) -> Result<Vec<Range<u32>>, String> {
+    if pattern.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let regex = RegexBuilder::new(pattern)
+        .case_insensitive(!case_sensitive)
+        .multi_line(true)
+        .build()
+        .map_err(|err| {
+            // Syntax errors draw a diagram over several lines, ending in the reason.
+            let err = err.to_string();
+            err.lines().last().unwrap_or_default().trim().to_string()
+        })?;
+
+    let mut chars = text.char_indices().peekable();
+    let mut unit = 0;
+    let mut to_unit = |byte: usize| {
+        while let Some((_, c)) = chars.next_if(|&(idx, _)| idx < byte) {
+            unit += c.len_utf16() as u32;
+        }
+        unit
+    };
+
+    Ok(regex
+        .find_iter(text)
+        .filter(|found| !found.is_empty())
+        .map(|found| to_unit(found.start())..to_unit(found.end()))
+        .collect())
+}
+
+pub fn in_ranges(ranges: &[Range<u32>], offset: u32) -> bool {
+    let idx = ranges.partition_point(|range| range.end <= offset);
+    ranges.get(idx).is_some_and(|range| range.contains(&offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+
+    #[wasm_bindgen_test]
+    fn replacing_surrogate_pairs_with_ascii_moves_the_caret_back() {
+        let text = "a😊b😊c";
+        let matches = find_matches(text, "😊", true);
+        assert_eq!(matches, [1..3, 4..6]);
+
+        // After the last match, the caret moves back a unit for each.
+        assert_eq!(
+            replace_ranges(text, &matches, "x", 6),
+            ("axbxc".to_string(), 4)
+        );
+        // Inside a match, it goes to the end of the replacement.
+        assert_eq!(
+            replace_ranges(text, &matches, "x", 2),
+            ("axbxc".to_string(), 2)
+        );
+        assert_eq!(
+            replace_ranges(text, &matches, "x", 0),
+            ("axbxc".to_string(), 0)
+        );
+        // Only the replaced matches move it.
+        assert_eq!(
+            replace_ranges(text, &matches[..1], "x", 7),
+            ("axb😊c".to_string(), 6)
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn case_folding_matches_cover_the_text_not_the_query() {
+        // The Kelvin sign lowercases to "k", which is two bytes shorter.
+        let text = "\u{212A}elvin and kelvin";
+        let matches = find_matches(text, "kelvin", false);
+        assert_eq!(matches, [0..6, 11..17]);
+        assert_eq!(find_matches(text, "kelvin", true), [11..17]);
+        assert_eq!(
+            replace_ranges(text, &matches, "K", 17),
+            ("K and K".to_string(), 7)
+        );
+
+        // "İ" lowercases to two characters, so it doesn't match "i" on its own.
+        assert_eq!(find_matches("İi", "i", false), [1..2]);
+        assert_eq!(find_matches("GROẞ groß", "groß", false), [0..4, 5..9]);
+    }
+
+    #[wasm_bindgen_test]
+    fn regex_matches_are_utf16_and_skip_empty_ones() {
+        assert_eq!(
+            find_regex_matches("😊ab\ncab", "^c|b$", true),
+            Ok(vec![3..4, 5..6, 7..8])
+        );
+        assert_eq!(find_regex_matches("AB", "b", false), Ok(vec![1..2]));
+        assert_eq!(find_regex_matches("ab", "x*", true), Ok(vec![]));
+        assert_eq!(
+            find_regex_matches("ab", "(", true),
+            Err("error: unclosed group".to_string())
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn stepping_wraps_around_the_document() {
+        let find = FindBar {
+            matches: vec![1..2, 5..6],
+            ..FindBar::default()
+        };
+        assert_eq!(find.step(&(0..0), true), Some(1..2));
+        assert_eq!(find.step(&(1..2), true), Some(5..6));
+        assert_eq!(find.step(&(5..6), true), Some(1..2));
+        assert_eq!(find.step(&(5..6), false), Some(1..2));
+        assert_eq!(find.step(&(1..2), false), Some(5..6));
+        assert_eq!(FindBar::default().step(&(0..0), true), None);
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::HashSet;
+
+use anyhow::Context;
+use ratatui_wgpu::{
+    Font,
+    Fonts,
+};
+use unicode_segmentation::UnicodeSegmentation;
+use wasm_bindgen::{
+    JsCast,
+    JsValue,
+};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::Response;
+use winit::event_loop::EventLoopProxy;
+
+use crate::{
+    app::AppEvent,
+    js_error,
+};
+
+pub const DEFAULT_FONT_SIZE_PX: u32 = 24;
+pub const MIN_FONT_SIZE_PX: u32 = 8;
+pub const MAX_FONT_SIZE_PX: u32 = 96;
+pub const FONT_SIZE_STEP_PX: u32 = 2;
+
+/// Alternative primary fonts, fetched relative to the page once the editor is
+/// running. Any that fail to load are left out of the rotation.
+pub const ALTERNATE_FONTS: &[(&str, &str)] = &[
+    ("JetBrains Mono", "fonts/JetBrainsMono-Regular.ttf"),
+    ("Fira Mono", "fonts/FiraMono-Regular.ttf"),
+];
+
+/// A font along with a parsed copy of its tables, which is used to check the
+/// characters it covers.
+#[derive(Clone)]
+pub struct LoadedFont {
+    pub name: &'static str,
+    pub font: Font<'static>,
+    pub face: ttf_parser::Face<'static>,
+}
+
+impl LoadedFont {
+    pub fn new(name: &'static str, data: &'static [u8]) -> Option<Self> {
+        Some(Self {
+            name,
+            font: Font::new(data)?,
+            face: ttf_parser::Face::parse(data, 0).ok()?,
+        })
+    }
+
+    pub fn covers(&self, c: char) -> bool {
+        self.face.glyph_index(c).is_some()
+    }
+}
+
+/// The primary fonts the user can cycle through, along with the fallbacks used
+/// for glyphs they don't cover, in the order they are tried.
+#[derive(Clone)]
+pub struct FontSet {
+    pub primary: Vec<LoadedFont>,
+    pub current: usize,
+    pub fallbacks: Vec<LoadedFont>,
+    pub size_px: u32,
+    /// Characters that have already been reported as missing.
+    pub reported: HashSet<char>,
+}
+
+impl FontSet {
+    pub fn current(&self) -> &LoadedFont {
+        &self.primary[self.current]
+    }
+
+    pub fn cycle(&mut self) {
+        self.current = (self.current + 1) % self.primary.len();
+    }
+
+    /// Sets the font size, returning whether it changed.
+    pub fn resize(&mut self, size_px: u32) -> bool {
+        let size_px = size_px.clamp(MIN_FONT_SIZE_PX, MAX_FONT_SIZE_PX);
+        std::mem::replace(&mut self.size_px, size_px) != size_px
+    }
+
+    pub fn fonts(&self) -> Fonts<'static> {
+        let mut fonts = Fonts::new(self.current().font.clone(), self.size_px);
+        fonts.add_fonts(self.fallbacks.iter().map(|font| font.font.clone()));
+        fonts
+    }
+
+    /// Logs each character in `text` that none of the active fonts can render.
+    pub fn report_missing(&mut self, text: &str) {
+        for grapheme in text.graphemes(true) {
+            // Only the base character is checked. Combining marks and joiners are often
+            // drawn without a glyph of their own.
+            let Some(c) = grapheme.chars().next() else {
+                continue;
+            };
+            if c.is_whitespace() || c.is_control() || self.reported.contains(&c) {
+                continue;
+            }
+
+            let covered = std::iter::once(self.current())
+                .chain(&self.fallbacks)
+                .any(|font| font.covers(c));
+            if !covered {
+                log::warn!(
+                    "No font can render {grapheme:?} (U+{:04X}), add a fallback font that covers it",
+                    c as u32
+                );
+                self.reported.insert(c);
+            }
+        }
+    }
+}
+
+pub async fn fetch_bytes(url: &str) -> Result<Vec<u8>, JsValue> {
+    let window = web_sys::window().ok_or_else(|| JsValue::from_str("no window"))?;
+    let response = JsFuture::from(window.fetch_with_str(url))
+        .await?
+        .dyn_into::<Response>()?;
+    if !response.ok() {
+        return Err(JsValue::from_str(&format!("HTTP {}", response.status())));
+    }
+
+    let buffer = JsFuture::from(response.array_buffer()?).await?;
+    Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+}
+
+pub async fn fetch_font(name: &'static str, url: &str) -> anyhow::Result<LoadedFont> {
+    let data = fetch_bytes(url).await.map_err(js_error)?;
+    // See `preload_fonts` for why the data is leaked.
+    LoadedFont::new(name, data.leak()).context("The file is not a usable font")
+}
+
+pub fn preload_fonts(proxy: EventLoopProxy<AppEvent>) {
+    for &(name, url) in ALTERNATE_FONTS {
+        let proxy = proxy.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            match fetch_bytes(url).await {
+                // Fonts borrow their data for as long as the backend uses them, which is
+                // the rest of the session.
+                Ok(data) => {
+                    let _ = proxy.send_event(AppEvent::FontLoaded {
+                        name,
+                        data: data.leak(),
+                    });
+                }
+                Err(err) => log::warn!("Failed to load {name} from {url}: {err:?}"),
+            }
+        });
+    }
+}
@@ -0,0 +1,382 @@
+use std::pin::Pin;
+
+use anyhow::anyhow;
+use ratatui::prelude::*;
+use ratatui_wgpu::{
+    Builder,
+    Dimensions,
+    PostProcessor,
+    WgpuBackend,
+    shaders::DefaultPostProcessor,
+};
+use wasm_bindgen::{
+    JsCast,
+    JsValue,
+};
+use web_sys::HtmlCanvasElement;
+use winit::event_loop::EventLoopProxy;
+
+use crate::{
+    app::AppEvent,
+    fonts::FontSet,
+    shader::{
+        Adjustment,
+        BLOOM_SHADER,
+        CRT_SHADER,
+        CrtSettings,
+        PLAIN_SHADER,
+        SCANLINES_SHADER,
+        ShaderPass,
+    },
+};
+
+pub type EditorBackend = WgpuBackend<'static, 'static, SwitchablePostProcessor>;
+
+/// The post-processing effects, in the order they are cycled through. Each
+/// costs one fullscreen pass per frame; what differs is how many times it
+/// samples the text texture per pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Effect {
+    /// Curvature, scanlines, vignette and color fringing. Three samples per
+    /// pixel.
+    #[default]
+    Crt,
+    /// Scanlines alone. One sample per pixel, about as cheap as plain.
+    Scanlines,
+    /// A glow around bright text. Twenty-six samples per pixel, by far the
+    /// most expensive, and worth avoiding on integrated GPUs at high
+    /// resolutions.
+    Bloom,
+    /// The page's own shader, from the `shader` query parameter. Its cost is
+    /// up to the shader.
+    Custom,
+    /// The text as drawn. One copy per pixel.
+    Plain,
+}
+
+impl Effect {
+    pub const ALL: [Self; 5] = [
+        Self::Crt,
+        Self::Scanlines,
+        Self::Bloom,
+        Self::Custom,
+        Self::Plain,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|effect| effect.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Crt => "crt",
+            Self::Scanlines => "scanlines",
+            Self::Bloom => "bloom",
+            Self::Custom => "custom",
+            Self::Plain => "plain",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let idx = Self::ALL.iter().position(|effect| *effect == self).unwrap();
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+}
+
+/// How the terminal's frames are finished before they reach the canvas.
+#[derive(Debug, Clone, Copy)]
+pub struct Effects {
+    pub effect: Effect,
+    pub crt_settings: CrtSettings,
+    pub reduced_motion: bool,
+    /// Draws plain for the high-contrast mode.
+    pub high_contrast: bool,
+    pub adjustment: Adjustment,
+}
+
+/// Every effect is compiled up front, so switching between them is just a
+/// matter of which one [`PostProcessor::process`] runs.
+pub struct SwitchablePostProcessor {
+    pub crt: ShaderPass,
+    pub scanlines: ShaderPass,
+    pub bloom: ShaderPass,
+    /// Missing when the page supplied no shader or the one it did failed to
+    /// compile.
+    pub custom: Option<ShaderPass>,
+    /// Resolves to the custom shader's compile error, if any, once the GPU has
+    /// checked it.
+    pub custom_error: Option<Pin<Box<dyn Future<Output = Option<wgpu::Error>>>>>,
+    pub plain: DefaultPostProcessor,
+    /// Stands in for `plain` when the brightness or contrast is adjusted,
+    /// which the default post-processor can't do.
+    pub adjusted: ShaderPass,
+    pub effect: Effect,
+    pub crt_settings: CrtSettings,
+    pub reduced_motion: bool,
+    pub high_contrast: bool,
+    pub adjustment: Adjustment,
+}
+
+impl SwitchablePostProcessor {
+    pub fn set_effect(&mut self, effect: Effect) {
+        self.effect = effect;
+    }
+
+    pub fn set_crt_settings(&mut self, settings: CrtSettings) {
+        self.crt_settings = settings;
+    }
+
+    pub fn set_effects(&mut self, effects: Effects) {
+        self.set_effect(effects.effect);
+        self.set_crt_settings(effects.crt_settings);
+        self.set_reduced_motion(effects.reduced_motion);
+        self.set_high_contrast(effects.high_contrast);
+        self.set_adjustment(effects.adjustment);
+    }
+
+    pub fn set_adjustment(&mut self, adjustment: Adjustment) {
+        self.adjustment = adjustment;
+    }
+
+    pub fn set_reduced_motion(&mut self, reduced_motion: bool) {
+        self.reduced_motion = reduced_motion;
+    }
+
+    pub fn set_high_contrast(&mut self, high_contrast: bool) {
+        self.high_contrast = high_contrast;
+    }
+
+    pub fn available(&self, effect: Effect) -> bool {
+        effect != Effect::Custom || self.custom.is_some()
+    }
+
+    /// The effect being drawn, which is plain when the user prefers reduced
+    /// motion or high contrast, or the chosen effect isn't available.
+    pub fn active_effect(&self) -> Effect {
+        if self.reduced_motion || self.high_contrast || !self.available(self.effect) {
+            Effect::Plain
+        } else {
+            self.effect
+        }
+    }
+}
+
+impl PostProcessor for SwitchablePostProcessor {
+    /// The source of a custom shader's `fs_main`.
+    type UserData = Option<String>;
+
+    fn compile(
+        device: &wgpu::Device,
+        text_view: &wgpu::TextureView,
+        surface_config: &wgpu::SurfaceConfiguration,
+        custom_source: Self::UserData,
+    ) -> Self {
+        // Catches the errors a bad custom shader causes, which would otherwise
+        // be uncaptured and panic.
+        let (custom, custom_error) = match custom_source {
+            Some(source) => {
+                device.push_error_scope(wgpu::ErrorFilter::Validation);
+                let pass = ShaderPass::new(device, text_view, surface_config, "custom", &source);
+                let error: Pin<Box<dyn Future<Output = _>>> = Box::pin(device.pop_error_scope());
+                (Some(pass), Some(error))
+            }
+            None => (None, None),
+        };
+
+        Self {
+            crt: ShaderPass::new(device, text_view, surface_config, "crt", CRT_SHADER),
+            scanlines: ShaderPass::new(
+                device,
+                text_view,
+                surface_config,
+                "scanlines",
+                SCANLINES_SHADER,
+            ),
+            bloom: ShaderPass::new(device, text_view, surface_config, "bloom", BLOOM_SHADER),
+            custom,
+            custom_error,
+            plain: DefaultPostProcessor::compile(
+                device,
+                text_view,
+                surface_config,
+                Default::default(),
+            ),
+            adjusted: ShaderPass::new(device, text_view, surface_config, "plain", PLAIN_SHADER),
+            effect: Effect::default(),
+            crt_settings: CrtSettings::default(),
+            reduced_motion: false,
+            high_contrast: false,
+            adjustment: Adjustment::default(),
+        }
+    }
+
+    fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        text_view: &wgpu::TextureView,
+        surface_config: &wgpu::SurfaceConfiguration,
+    ) {
+        self.crt.resize(device, text_view);
+        self.scanlines.resize(device, text_view);
+        self.bloom.resize(device, text_view);
+        if let Some(custom) = &mut self.custom {
+            custom.resize(device, text_view);
+        }
+        self.plain.resize(device, text_view, surface_config);
+        self.adjusted.resize(device, text_view);
+    }
+
+    fn process(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        text_view: &wgpu::TextureView,
+        surface_config: &wgpu::SurfaceConfiguration,
+        surface_view: &wgpu::TextureView,
+    ) {
+        let pass = match self.active_effect() {
+            Effect::Crt => &self.crt,
+            Effect::Scanlines => &self.scanlines,
+            Effect::Bloom => &self.bloom,
+            Effect::Custom => self.custom.as_ref().unwrap(),
+            Effect::Plain if !self.adjustment.is_neutral() => &self.adjusted,
+            Effect::Plain => {
+                self.plain
+                    .process(encoder, queue, text_view, surface_config, surface_view);
+                return;
+            }
+        };
+        pass.process(
+            encoder,
+            queue,
+            surface_config,
+            surface_view,
+            self.crt_settings,
+            self.adjustment,
+        );
+    }
+
+    fn needs_update(&self) -> bool {
+        // The shader effects are still, so they only need redrawing with the
+        // text.
+        self.active_effect() == Effect::Plain
+            && self.adjustment.is_neutral()
+            && self.plain.needs_update()
+    }
+}
+
+pub fn webgpu_available() -> bool {
+    let Some(navigator) = web_sys::window().map(|win| win.navigator()) else {
+        return false;
+    };
+    log::info!("User agent: {}", navigator.user_agent().unwrap_or_default());
+
+    js_sys::Reflect::get(&navigator, &JsValue::from_str("gpu"))
+        .is_ok_and(|gpu| !gpu.is_undefined() && !gpu.is_null())
+}
+
+pub const REDUCED_MOTION_QUERY: &str = "(prefers-reduced-motion: reduce)";
+
+/// Builds the terminal on a new device. If the device is later lost, a
+/// [`AppEvent::DeviceLost`] is sent so the terminal can be rebuilt.
+pub async fn build_terminal(
+    canvas: HtmlCanvasElement,
+    fonts: &FontSet,
+    backends: wgpu::Backends,
+    size: Dimensions,
+    effects: Effects,
+    custom_shader: Option<String>,
+    proxy: EventLoopProxy<AppEvent>,
+) -> anyhow::Result<Terminal<EditorBackend>> {
+    let webgl = backends == wgpu::Backends::GL;
+    let mut builder = Builder::from_font(fonts.current().font.clone())
+        .with_fonts(
+            fonts
+                .fallbacks
+                .iter()
+                .map(|font| font.font.clone())
+                .collect(),
+        )
+        .with_font_size_px(fonts.size_px)
+        .with_width_and_height(size)
+        .with_user_data(custom_shader)
+        .with_instance(wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends,
+            ..Default::default()
+        }));
+    if webgl {
+        builder = builder.with_limits(wgpu::Limits::downlevel_webgl2_defaults());
+    }
+    let api = if webgl { "WebGL2" } else { "WebGPU" };
+    let wgpu_backend = builder
+        .build_with_target(wgpu::SurfaceTarget::Canvas(canvas))
+        .await
+        .map_err(|err| anyhow!("{api} unavailable: {err:?}"))?;
+
+    let mut terminal = Terminal::new(wgpu_backend)?;
+    let post_processor = terminal.backend_mut().post_processor_mut();
+    if let Some(error) = post_processor.custom_error.take()
+        && let Some(error) = error.await
+    {
+        log::error!("Custom shader failed to compile: {error}");
+        post_processor.custom = None;
+        // Messages are dropped until the caller has stored the terminal, which
+        // it does before this task gets to run.
+        let proxy = proxy.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let _ = proxy.send_event(AppEvent::Message(format!(
+                "The custom shader failed to compile: {error}"
+            )));
+        });
+    }
+
+    terminal
+        .backend()
+        .device()
+        .set_device_lost_callback(move |reason, message| {
+            // Dropping the old terminal destroys its device, which isn't worth
+            // reconnecting for.
+            if reason != wgpu::DeviceLostReason::Destroyed {
+                let _ = proxy.send_event(AppEvent::DeviceLost(message));
+            }
+        });
+    terminal
+        .backend_mut()
+        .post_processor_mut()
+        .set_effects(effects);
+    Ok(terminal)
+}
+
+/// Picks the graphics API to render with. WebGPU is preferred, but browsers
+/// without it can still run the editor on WebGL2. The CRT effect is a WGSL
+/// shader that wgpu translates for WebGL, so it is available either way.
+pub async fn select_backends() -> Option<wgpu::Backends> {
+    if webgpu_available() {
+        // The adapter is checked without a surface because the canvas can't be
+        // switched to WebGL once it has a WebGPU context.
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::BROWSER_WEBGPU,
+            ..Default::default()
+        });
+        if instance
+            .request_adapter(&wgpu::RequestAdapterOptions::default())
+            .await
+            .is_ok()
+        {
+            return Some(wgpu::Backends::BROWSER_WEBGPU);
+        }
+        log::warn!("WebGPU is exposed but has no adapter, falling back to WebGL2");
+    }
+
+    webgl2_available().then_some(wgpu::Backends::GL)
+}
+
+pub fn webgl2_available() -> bool {
+    // Probes a throwaway canvas so the editor's own is left without a context.
+    web_sys::window()
+        .and_then(|win| win.document())
+        .and_then(|doc| doc.create_element("canvas").ok())
+        .and_then(|canvas| canvas.dyn_into::<HtmlCanvasElement>().ok())
+        .and_then(|canvas| canvas.get_context("webgl2").ok().flatten())
+        .is_some()
+}
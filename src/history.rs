@@ -0,0 +1,209 @@
+use std::{
+    collections::VecDeque,
+    ops::Range,
+};
+
+/// Typing that pauses for less than this is undone as a single step.
+pub const COALESCE_MS: f64 = 1000.0;
+/// The most undo steps kept. The oldest are dropped first.
+pub const MAX_UNDO: usize = 500;
+/// The most text the undo steps hold between them, since each is a copy of
+/// the whole document. The oldest are dropped first, though the last step is
+/// always kept.
+pub const MAX_UNDO_BYTES: usize = 32 * 1024 * 1024;
+
+/// The document and selection at some point in its history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub text: String,
+    pub selection: Range<u32>,
+}
+
+/// Undo and redo for the document. The textarea keeps its own history, but
+/// it's inconsistent between browsers and is lost whenever the value is set
+/// programmatically, which the replace and paste handlers do.
+#[derive(Default)]
+pub struct History {
+    /// The state the document was last seen in.
+    current: Option<Snapshot>,
+    undo: VecDeque<Snapshot>,
+    /// The length of the text in `undo`.
+    undo_bytes: usize,
+    redo: Vec<Snapshot>,
+    /// When the last typed edit was recorded, if the last edit was typed.
+    last_typed: Option<f64>,
+}
+
+impl History {
+    /// The state the document was last recorded in.
+    pub fn current(&self) -> Option<&Snapshot> {
+        self.current.as_ref()
+    }
+
+    /// Records the document after a change. Typed edits made in quick
+    /// succession are merged with the ones before them.
+    pub fn record(&mut self, snapshot: Snapshot, now: f64, typed: bool) {
+        let Some(current) = self.current.take() else {
+            self.current = Some(snapshot);
+            return;
+        };
+        if current.text == snapshot.text {
+            self.current = Some(snapshot);
+            return;
+        }
+
+        let merge = typed
+            && !self.undo.is_empty()
+            && self
+                .last_typed
+                .is_some_and(|last_typed| now - last_typed < COALESCE_MS);
+        if !merge {
+            self.push_undo(current);
+        }
+        self.redo.clear();
+        self.current = Some(snapshot);
+        self.last_typed = typed.then_some(now);
+    }
+
+    fn push_undo(&mut self, snapshot: Snapshot) {
+        self.undo_bytes += snapshot.text.len();
+        self.undo.push_back(snapshot);
+        while self.undo.len() > MAX_UNDO
+            || (self.undo_bytes > MAX_UNDO_BYTES && self.undo.len() > 1)
+        {
+            let dropped = self.undo.pop_front().unwrap();
+            self.undo_bytes -= dropped.text.len();
+        }
+    }
+
+    /// Steps back, returning the state to restore.
+    pub fn undo(&mut self) -> Option<&Snapshot> {
+        let previous = self.undo.pop_back()?;
+        self.undo_bytes -= previous.text.len();
+        self.redo.extend(self.current.replace(previous));
+        self.last_typed = None;
+        self.current.as_ref()
+    }
+
+    /// Steps forward again after an undo, returning the state to restore.
+    pub fn redo(&mut self) -> Option<&Snapshot> {
+        let next = self.redo.pop()?;
+        if let Some(current) = self.current.replace(next) {
+            self.push_undo(current);
+        }
+        self.last_typed = None;
+        self.current.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+
+    fn snapshot(text: &str) -> Snapshot {
+        Snapshot {
+            text: text.to_string(),
+            selection: 0..0,
+        }
+    }
+
+    fn text(snapshot: Option<&Snapshot>) -> Option<&str> {
+        snapshot.map(|snapshot| snapshot.text.as_str())
+    }
+
+    #[wasm_bindgen_test]
+    fn quick_typing_is_undone_in_one_step() {
+        let mut history = History::default();
+        history.record(snapshot(""), 0.0, false);
+        history.record(snapshot("a"), 0.0, true);
+        history.record(snapshot("ab"), COALESCE_MS / 2.0, true);
+        history.record(snapshot("abc"), COALESCE_MS, true);
+        // A pause starts a new step.
+        history.record(snapshot("abcd"), COALESCE_MS * 3.0, true);
+
+        assert_eq!(text(history.undo()), Some("abc"));
+        assert_eq!(text(history.undo()), Some(""));
+        assert_eq!(history.undo(), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn edits_that_are_not_typed_are_never_merged() {
+        let mut history = History::default();
+        history.record(snapshot(""), 0.0, false);
+        history.record(snapshot("a"), 0.0, true);
+        history.record(snapshot("a b"), 1.0, false);
+        history.record(snapshot("a bc"), 2.0, true);
+
+        assert_eq!(text(history.undo()), Some("a b"));
+        assert_eq!(text(history.undo()), Some("a"));
+        assert_eq!(text(history.undo()), Some(""));
+    }
+
+    #[wasm_bindgen_test]
+    fn selection_changes_alone_are_not_steps() {
+        let mut history = History::default();
+        history.record(snapshot("a"), 0.0, false);
+        history.record(
+            Snapshot {
+                selection: 0..1,
+                ..snapshot("a")
+            },
+            1.0,
+            false,
+        );
+        assert_eq!(history.undo(), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn redo_returns_what_was_undone_until_the_next_edit() {
+        let mut history = History::default();
+        history.record(snapshot(""), 0.0, false);
+        history.record(snapshot("a"), 0.0, false);
+        history.record(snapshot("ab"), 0.0, false);
+
+        assert_eq!(text(history.undo()), Some("a"));
+        assert_eq!(text(history.undo()), Some(""));
+        assert_eq!(text(history.redo()), Some("a"));
+        assert_eq!(text(history.redo()), Some("ab"));
+        assert_eq!(history.redo(), None);
+
+        assert_eq!(text(history.undo()), Some("a"));
+        history.record(snapshot("ax"), 0.0, false);
+        assert_eq!(history.redo(), None);
+        assert_eq!(text(history.undo()), Some("a"));
+    }
+
+    #[wasm_bindgen_test]
+    fn the_oldest_steps_are_dropped_past_the_cap() {
+        let mut history = History::default();
+        for step in 0..=MAX_UNDO + 10 {
+            history.record(snapshot(&step.to_string()), 0.0, false);
+        }
+
+        let mut undone = 0;
+        let mut oldest = None;
+        while let Some(snapshot) = history.undo() {
+            oldest = Some(snapshot.text.clone());
+            undone += 1;
+        }
+        assert_eq!(undone, MAX_UNDO);
+        assert_eq!(oldest.as_deref(), Some("10"));
+    }
+
+    #[wasm_bindgen_test]
+    fn the_oldest_steps_are_dropped_past_the_byte_cap() {
+        let len = MAX_UNDO_BYTES / 2 + 1;
+        let mut history = History::default();
+        for letter in ["a", "b", "c"] {
+            history.record(snapshot(&letter.repeat(len)), 0.0, false);
+        }
+
+        assert_eq!(
+            history.undo().map(|snapshot| &snapshot.text[..1]),
+            Some("b")
+        );
+        assert_eq!(history.undo(), None);
+    }
+}
@@ -0,0 +1,1190 @@
+use std::{
+    num::NonZeroU32,
+    ops::Range,
+};
+
+use unicode_segmentation::UnicodeSegmentation;
+use wasm_bindgen::{
+    JsCast,
+    closure::Closure,
+};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    AddEventListenerOptions,
+    ClipboardEvent,
+    CompositionEvent,
+    CssStyleDeclaration,
+    Document,
+    DragEvent,
+    Element,
+    File,
+    HtmlCanvasElement,
+    HtmlInputElement,
+    HtmlTextAreaElement,
+    InputEvent,
+    KeyboardEvent,
+    MediaQueryListEvent,
+};
+use winit::event_loop::EventLoopProxy;
+
+use crate::{
+    app::AppEvent,
+    edit::PAIRS,
+    render::Row,
+    storage::{
+        FileHandle,
+        clipboard,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    ToggleCrt,
+    CycleEffect,
+    StrengthenCrt,
+    WeakenCrt,
+    Brighten,
+    Dim,
+    IncreaseContrast,
+    DecreaseContrast,
+    ResetAdjustment,
+    Save,
+    CycleFont,
+    ZoomIn,
+    ZoomOut,
+    ZoomReset,
+    Find,
+    FindNext,
+    FindPrevious,
+    ToggleFindCase,
+    ToggleFindRegex,
+    CloseFind,
+    OpenReplace,
+    SwitchFindField,
+    ReplaceNext,
+    ReplaceAll,
+    ToggleWhitespace,
+    ToggleTrailingWhitespace,
+    ToggleRuler,
+    ToggleLineNumbers,
+    ToggleWrap,
+    OpenPalette,
+    PaletteUp,
+    PaletteDown,
+    RunPaletteCommand,
+    ClosePalette,
+    OpenGoToLine,
+    ConfirmGoToLine,
+    CloseGoToLine,
+    ToggleFrameStats,
+    ToggleFullscreen,
+    Copy,
+    Undo,
+    Redo,
+    SortLines,
+    UniqueLines,
+    Uppercase,
+    Lowercase,
+    TitleCase,
+    Base64Encode,
+    Base64Decode,
+    UrlEncode,
+    UrlDecode,
+    ToggleTypewriter,
+    UseLf,
+    UseCrlf,
+    PrettifyJson,
+    MinifyJson,
+    ToggleMinimap,
+    SplitSideBySide,
+    SplitStacked,
+    CloseSplit,
+    SwitchPane,
+    ToggleHelp,
+    CloseHelp,
+    CycleLineSpacing,
+    ToggleAutoClose,
+    Newline,
+    ToggleComment,
+    MoveLinesUp,
+    MoveLinesDown,
+    DuplicateLines,
+    NewDocument,
+    ResetToSample,
+    OpenFile,
+    Confirm,
+    CancelConfirm,
+    ToggleDiff,
+    ToggleHighContrast,
+    CycleTheme,
+    MoveCaret(Motion),
+    /// Moves the caret while keeping the selection's other end in place.
+    ExtendSelection(Motion),
+}
+
+/// Where the navigation keys move the caret. Lines are the rows on screen, so
+/// a wrapped line is moved through a row at a time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    PageUp,
+    PageDown,
+    LineStart,
+    LineEnd,
+    DocumentStart,
+    DocumentEnd,
+}
+
+pub struct KeyBinding {
+    pub key: &'static str,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    pub action: Action,
+}
+
+pub const KEY_BINDINGS: &[KeyBinding] = &[
+    KeyBinding {
+        key: "F1",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::ToggleHelp,
+    },
+    KeyBinding {
+        key: "F2",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::ToggleCrt,
+    },
+    KeyBinding {
+        key: "F2",
+        ctrl: false,
+        shift: true,
+        alt: false,
+        action: Action::CycleEffect,
+    },
+    KeyBinding {
+        key: "F3",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::ToggleFrameStats,
+    },
+    KeyBinding {
+        key: "s",
+        ctrl: true,
+        shift: false,
+        alt: false,
+        action: Action::Save,
+    },
+    // Browsers keep Ctrl+N for a new window, and the page never sees it.
+    KeyBinding {
+        key: "n",
+        ctrl: false,
+        shift: false,
+        alt: true,
+        action: Action::NewDocument,
+    },
+    KeyBinding {
+        key: "o",
+        ctrl: true,
+        shift: false,
+        alt: false,
+        action: Action::OpenFile,
+    },
+    KeyBinding {
+        key: "F4",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::CycleFont,
+    },
+    KeyBinding {
+        key: "=",
+        ctrl: true,
+        shift: false,
+        alt: false,
+        action: Action::ZoomIn,
+    },
+    KeyBinding {
+        key: "+",
+        ctrl: true,
+        shift: true,
+        alt: false,
+        action: Action::ZoomIn,
+    },
+    KeyBinding {
+        key: "-",
+        ctrl: true,
+        shift: false,
+        alt: false,
+        action: Action::ZoomOut,
+    },
+    KeyBinding {
+        key: "0",
+        ctrl: true,
+        shift: false,
+        alt: false,
+        action: Action::ZoomReset,
+    },
+    KeyBinding {
+        key: "f",
+        ctrl: true,
+        shift: false,
+        alt: false,
+        action: Action::Find,
+    },
+    KeyBinding {
+        key: "h",
+        ctrl: true,
+        shift: false,
+        alt: false,
+        action: Action::OpenReplace,
+    },
+    KeyBinding {
+        key: "F6",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::ToggleWhitespace,
+    },
+    KeyBinding {
+        key: "F7",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::ToggleTrailingWhitespace,
+    },
+    KeyBinding {
+        key: "F8",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::ToggleRuler,
+    },
+    KeyBinding {
+        key: "F9",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::ToggleDiff,
+    },
+    KeyBinding {
+        key: "F11",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::ToggleFullscreen,
+    },
+    KeyBinding {
+        key: "p",
+        ctrl: true,
+        shift: true,
+        alt: false,
+        action: Action::OpenPalette,
+    },
+    KeyBinding {
+        key: "g",
+        ctrl: true,
+        shift: false,
+        alt: false,
+        action: Action::OpenGoToLine,
+    },
+    KeyBinding {
+        key: "\\",
+        ctrl: true,
+        shift: false,
+        alt: false,
+        action: Action::SplitSideBySide,
+    },
+];
+
+/// Keys handled by the editor itself, ahead of `KEY_BINDINGS`. The prompts
+/// leave these to the browser so they act on the prompt's own text.
+pub const EDITOR_BINDINGS: &[KeyBinding] = &[
+    KeyBinding {
+        key: "c",
+        ctrl: true,
+        shift: false,
+        alt: false,
+        action: Action::Copy,
+    },
+    KeyBinding {
+        key: "z",
+        ctrl: true,
+        shift: false,
+        alt: false,
+        action: Action::Undo,
+    },
+    KeyBinding {
+        key: "z",
+        ctrl: true,
+        shift: true,
+        alt: false,
+        action: Action::Redo,
+    },
+    KeyBinding {
+        key: "y",
+        ctrl: true,
+        shift: false,
+        alt: false,
+        action: Action::Redo,
+    },
+    KeyBinding {
+        key: "Escape",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::CloseHelp,
+    },
+    KeyBinding {
+        key: "Enter",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::Newline,
+    },
+    KeyBinding {
+        key: "/",
+        ctrl: true,
+        shift: false,
+        alt: false,
+        action: Action::ToggleComment,
+    },
+    KeyBinding {
+        key: "ArrowUp",
+        ctrl: false,
+        shift: false,
+        alt: true,
+        action: Action::MoveLinesUp,
+    },
+    KeyBinding {
+        key: "ArrowDown",
+        ctrl: false,
+        shift: false,
+        alt: true,
+        action: Action::MoveLinesDown,
+    },
+    KeyBinding {
+        key: "d",
+        ctrl: true,
+        shift: false,
+        alt: false,
+        action: Action::DuplicateLines,
+    },
+    KeyBinding {
+        key: "ArrowDown",
+        ctrl: false,
+        shift: true,
+        alt: true,
+        action: Action::DuplicateLines,
+    },
+    KeyBinding {
+        key: "z",
+        ctrl: false,
+        shift: false,
+        alt: true,
+        action: Action::ToggleWrap,
+    },
+    KeyBinding {
+        key: "PageUp",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::MoveCaret(Motion::PageUp),
+    },
+    KeyBinding {
+        key: "PageDown",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::MoveCaret(Motion::PageDown),
+    },
+    KeyBinding {
+        key: "Home",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::MoveCaret(Motion::LineStart),
+    },
+    KeyBinding {
+        key: "End",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::MoveCaret(Motion::LineEnd),
+    },
+    KeyBinding {
+        key: "Home",
+        ctrl: true,
+        shift: false,
+        alt: false,
+        action: Action::MoveCaret(Motion::DocumentStart),
+    },
+    KeyBinding {
+        key: "End",
+        ctrl: true,
+        shift: false,
+        alt: false,
+        action: Action::MoveCaret(Motion::DocumentEnd),
+    },
+    KeyBinding {
+        key: "PageUp",
+        ctrl: false,
+        shift: true,
+        alt: false,
+        action: Action::ExtendSelection(Motion::PageUp),
+    },
+    KeyBinding {
+        key: "PageDown",
+        ctrl: false,
+        shift: true,
+        alt: false,
+        action: Action::ExtendSelection(Motion::PageDown),
+    },
+    KeyBinding {
+        key: "Home",
+        ctrl: false,
+        shift: true,
+        alt: false,
+        action: Action::ExtendSelection(Motion::LineStart),
+    },
+    KeyBinding {
+        key: "End",
+        ctrl: false,
+        shift: true,
+        alt: false,
+        action: Action::ExtendSelection(Motion::LineEnd),
+    },
+    KeyBinding {
+        key: "Home",
+        ctrl: true,
+        shift: true,
+        alt: false,
+        action: Action::ExtendSelection(Motion::DocumentStart),
+    },
+    KeyBinding {
+        key: "End",
+        ctrl: true,
+        shift: true,
+        alt: false,
+        action: Action::ExtendSelection(Motion::DocumentEnd),
+    },
+];
+
+/// Keys handled by the find bar while it has focus, ahead of `KEY_BINDINGS`.
+pub const FIND_BINDINGS: &[KeyBinding] = &[
+    KeyBinding {
+        key: "Tab",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::SwitchFindField,
+    },
+    KeyBinding {
+        key: "Tab",
+        ctrl: false,
+        shift: true,
+        alt: false,
+        action: Action::SwitchFindField,
+    },
+    KeyBinding {
+        key: "Enter",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::FindNext,
+    },
+    KeyBinding {
+        key: "Enter",
+        ctrl: false,
+        shift: true,
+        alt: false,
+        action: Action::FindPrevious,
+    },
+    KeyBinding {
+        key: "c",
+        ctrl: false,
+        shift: false,
+        alt: true,
+        action: Action::ToggleFindCase,
+    },
+    KeyBinding {
+        key: "r",
+        ctrl: false,
+        shift: false,
+        alt: true,
+        action: Action::ToggleFindRegex,
+    },
+    KeyBinding {
+        key: "Escape",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::CloseFind,
+    },
+];
+
+/// Keys handled by the command palette while it has focus.
+pub const PALETTE_BINDINGS: &[KeyBinding] = &[
+    KeyBinding {
+        key: "ArrowUp",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::PaletteUp,
+    },
+    KeyBinding {
+        key: "ArrowDown",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::PaletteDown,
+    },
+    KeyBinding {
+        key: "Enter",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::RunPaletteCommand,
+    },
+    KeyBinding {
+        key: "Escape",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::ClosePalette,
+    },
+];
+
+/// Keys handled by the go to line prompt while it has focus.
+pub const GO_TO_LINE_BINDINGS: &[KeyBinding] = &[
+    KeyBinding {
+        key: "Enter",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::ConfirmGoToLine,
+    },
+    KeyBinding {
+        key: "Escape",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::CloseGoToLine,
+    },
+];
+
+/// Keys handled while a confirmation is asked for, ahead of `KEY_BINDINGS`.
+pub const CONFIRM_BINDINGS: &[KeyBinding] = &[
+    KeyBinding {
+        key: "Enter",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::Confirm,
+    },
+    KeyBinding {
+        key: "Escape",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::CancelConfirm,
+    },
+];
+
+/// Keys handled by the replacement field, ahead of `FIND_BINDINGS`.
+pub const REPLACE_BINDINGS: &[KeyBinding] = &[
+    KeyBinding {
+        key: "Enter",
+        ctrl: false,
+        shift: false,
+        alt: false,
+        action: Action::ReplaceNext,
+    },
+    KeyBinding {
+        key: "Enter",
+        ctrl: true,
+        shift: false,
+        alt: false,
+        action: Action::ReplaceAll,
+    },
+];
+
+impl KeyBinding {
+    pub fn label(&self) -> String {
+        let mut label = String::new();
+        if self.ctrl {
+            label.push_str("Ctrl+");
+        }
+        if self.alt {
+            label.push_str("Alt+");
+        }
+        if self.shift {
+            label.push_str("Shift+");
+        }
+        if self.key.chars().count() == 1 {
+            label.push_str(&self.key.to_uppercase());
+        } else {
+            label.push_str(self.key);
+        }
+        label
+    }
+}
+
+pub fn shortcut_for(action: Action) -> Option<String> {
+    EDITOR_BINDINGS
+        .iter()
+        .chain(KEY_BINDINGS)
+        .find(|binding| binding.action == action)
+        .map(KeyBinding::label)
+}
+
+pub fn action_for_key(bindings: &[KeyBinding], event: &KeyboardEvent) -> Option<Action> {
+    action_for(
+        bindings,
+        &event.key(),
+        event.ctrl_key() || event.meta_key(),
+        event.shift_key(),
+        event.alt_key(),
+    )
+}
+
+/// The action bound to `key` with exactly these modifiers held. Letters match
+/// either case, since Shift changes the key the browser reports.
+pub fn action_for(
+    bindings: &[KeyBinding],
+    key: &str,
+    ctrl: bool,
+    shift: bool,
+    alt: bool,
+) -> Option<Action> {
+    bindings
+        .iter()
+        .find(|binding| {
+            binding.key.eq_ignore_ascii_case(key)
+                && binding.ctrl == ctrl
+                && binding.shift == shift
+                && binding.alt == alt
+        })
+        .map(|binding| binding.action)
+}
+
+/// Pastes at least this large are inserted by the app rather than the browser,
+/// so the document is only reprocessed once.
+pub const LARGE_PASTE_BYTES: usize = 64 * 1024;
+
+/// Creates the hidden textarea the document is edited in. The canvas can't be
+/// read by assistive technology, so the textarea is what screen readers see of
+/// the editor, and is labelled with `label`.
+pub fn attach_editor(
+    doc: &Document,
+    dst: &Element,
+    canvas: &HtmlCanvasElement,
+    initial_text: &str,
+    label: &str,
+    proxy: EventLoopProxy<AppEvent>,
+) -> Option<(HtmlTextAreaElement, NonZeroU32, NonZeroU32)> {
+    let input = doc
+        .create_element("textarea")
+        .ok()?
+        .dyn_into::<HtmlTextAreaElement>()
+        .ok()?;
+    input.set_value(initial_text);
+    for (name, value) in [
+        ("role", "textbox"),
+        ("aria-label", label),
+        ("aria-multiline", "true"),
+        ("spellcheck", "false"),
+        ("autocapitalize", "off"),
+        ("autocorrect", "off"),
+    ] {
+        input.set_attribute(name, value).ok()?;
+    }
+
+    hide_input(&input.style())?;
+    dst.append_child(&input).ok()?;
+
+    // Text being composed is already in the value, but it isn't an edit until
+    // the composition ends.
+    let input_proxy = proxy.clone();
+    let on_input = Closure::<dyn FnMut(InputEvent)>::new(move |event: InputEvent| {
+        if !event.is_composing() {
+            let _ = input_proxy.send_event(AppEvent::Edited);
+        }
+    });
+    input
+        .add_event_listener_with_callback("input", on_input.as_ref().unchecked_ref())
+        .ok()?;
+    on_input.forget();
+
+    for name in ["compositionstart", "compositionupdate"] {
+        let composition_proxy = proxy.clone();
+        let on_composition =
+            Closure::<dyn FnMut(CompositionEvent)>::new(move |event: CompositionEvent| {
+                let _ = composition_proxy
+                    .send_event(AppEvent::Composing(event.data().unwrap_or_default()));
+            });
+        input
+            .add_event_listener_with_callback(name, on_composition.as_ref().unchecked_ref())
+            .ok()?;
+        on_composition.forget();
+    }
+    // Which is also sent when the composition is cancelled, after its text has
+    // been taken back out of the value.
+    let composition_proxy = proxy.clone();
+    let on_composition_end = Closure::<dyn FnMut()>::new(move || {
+        let _ = composition_proxy.send_event(AppEvent::CompositionEnded);
+    });
+    input
+        .add_event_listener_with_callback(
+            "compositionend",
+            on_composition_end.as_ref().unchecked_ref(),
+        )
+        .ok()?;
+    on_composition_end.forget();
+
+    let paste_proxy = proxy.clone();
+    let on_paste = Closure::<dyn FnMut(ClipboardEvent)>::new(move |event: ClipboardEvent| {
+        let Some(text) = event
+            .clipboard_data()
+            .and_then(|data| data.get_data("text/plain").ok())
+        else {
+            return;
+        };
+        if text.len() >= LARGE_PASTE_BYTES {
+            event.prevent_default();
+            let _ = paste_proxy.send_event(AppEvent::Paste(text));
+        }
+    });
+    input
+        .add_event_listener_with_callback("paste", on_paste.as_ref().unchecked_ref())
+        .ok()?;
+    on_paste.forget();
+
+    // Brackets and quotes are left to the app, which decides whether to close
+    // them. Characters being composed are still the IME's.
+    let pair_proxy = proxy.clone();
+    let on_before_input = Closure::<dyn FnMut(InputEvent)>::new(move |event: InputEvent| {
+        if event.input_type() != "insertText" || event.is_composing() {
+            return;
+        }
+        let Some(typed) = event.data().and_then(|data| {
+            let mut chars = data.chars();
+            chars.next().filter(|_| chars.next().is_none())
+        }) else {
+            return;
+        };
+        if PAIRS
+            .iter()
+            .any(|&(open, close)| typed == open || typed == close)
+        {
+            event.prevent_default();
+            let _ = pair_proxy.send_event(AppEvent::TypedPair(typed));
+        }
+    });
+    input
+        .add_event_listener_with_callback("beforeinput", on_before_input.as_ref().unchecked_ref())
+        .ok()?;
+    on_before_input.forget();
+
+    let on_keydown = Closure::<dyn FnMut(KeyboardEvent)>::new(move |event: KeyboardEvent| {
+        // Enter while composing commits the composition rather than breaking
+        // the line.
+        if event.is_composing() {
+            return;
+        }
+        if let Some(action) = [EDITOR_BINDINGS, KEY_BINDINGS]
+            .iter()
+            .find_map(|bindings| action_for_key(bindings, &event))
+        {
+            // Copying is left to the browser too, which copies the textarea's
+            // selection itself, so it still works wherever writing to the
+            // clipboard directly is refused.
+            if action != Action::Copy {
+                event.prevent_default();
+            } else if clipboard().is_none() {
+                return;
+            }
+            let _ = proxy.send_event(AppEvent::Action(action));
+        }
+    });
+    input
+        .add_event_listener_with_callback("keydown", on_keydown.as_ref().unchecked_ref())
+        .ok()?;
+    on_keydown.forget();
+
+    let style = canvas.style();
+    style.set_property("display", "block").ok()?;
+    style.set_property("width", "100%").ok()?;
+    style.set_property("height", "100%").ok()?;
+    style.set_property("position", "absolute").ok()?;
+    style.set_property("top", "0").ok()?;
+    style.set_property("left", "0").ok()?;
+    style.set_property("z-index", "1").ok()?;
+
+    dst.append_with_node_1(&Element::from(canvas.clone()))
+        .ok()?;
+
+    // The bounding rect is in CSS pixels, but the surface should match the canvas'
+    // backing store so text stays crisp on HiDPI screens.
+    let scale = web_sys::window().map_or(1.0, |win| win.device_pixel_ratio());
+    let bounds = canvas.get_bounding_client_rect();
+    Some((
+        input,
+        // The container may not be laid out yet, so fall back to a 1x1 surface until the first
+        // resize arrives instead of failing to start.
+        NonZeroU32::new((bounds.height() * scale) as u32).unwrap_or(NonZeroU32::MIN),
+        NonZeroU32::new((bounds.width() * scale) as u32).unwrap_or(NonZeroU32::MIN),
+    ))
+}
+
+/// Text is typed into inputs that are kept focused but out of sight, and drawn
+/// by the terminal instead.
+pub fn hide_input(style: &CssStyleDeclaration) -> Option<()> {
+    style.set_property("opacity", "0").ok()?;
+    style.set_property("width", "100%").ok()?;
+    style.set_property("height", "1px").ok()?;
+    style.set_property("position", "absolute").ok()?;
+    style.set_property("top", "0").ok()?;
+    style.set_property("left", "0").ok()?;
+    style.set_property("z-index", "-1").ok()?;
+    Some(())
+}
+
+/// Creates a region screen readers announce whatever is put in it, for the
+/// messages only drawn on the canvas. It is moved off screen rather than
+/// hidden, since hidden regions aren't announced.
+pub fn attach_live_region(doc: &Document, dst: &Element) -> Option<Element> {
+    let region = doc.create_element("div").ok()?;
+    for (name, value) in [
+        ("role", "status"),
+        ("aria-live", "polite"),
+        ("aria-atomic", "true"),
+        (
+            "style",
+            "position: absolute; left: -10000px; width: 1px; height: 1px; overflow: hidden",
+        ),
+    ] {
+        region.set_attribute(name, value).ok()?;
+    }
+    dst.append_child(&region).ok()?;
+    Some(region)
+}
+
+/// Creates a hidden input for typing into a prompt. Keys are looked up in each
+/// of `bindings` in turn.
+pub fn attach_prompt_input(
+    doc: &Document,
+    dst: &Element,
+    label: &str,
+    proxy: EventLoopProxy<AppEvent>,
+    bindings: &'static [&'static [KeyBinding]],
+    to_event: fn(String) -> AppEvent,
+) -> Option<HtmlInputElement> {
+    let input = doc
+        .create_element("input")
+        .ok()?
+        .dyn_into::<HtmlInputElement>()
+        .ok()?;
+    input.set_attribute("aria-label", label).ok()?;
+    hide_input(&input.style())?;
+    dst.append_child(&input).ok()?;
+
+    let target = input.clone();
+    let input_proxy = proxy.clone();
+    let on_input = Closure::<dyn FnMut()>::new(move || {
+        let _ = input_proxy.send_event(to_event(target.value()));
+    });
+    input
+        .add_event_listener_with_callback("input", on_input.as_ref().unchecked_ref())
+        .ok()?;
+    on_input.forget();
+
+    let on_keydown = Closure::<dyn FnMut(KeyboardEvent)>::new(move |event: KeyboardEvent| {
+        // As in the editor, Enter and Escape while composing belong to the IME.
+        if event.is_composing() {
+            return;
+        }
+        if let Some(action) = bindings
+            .iter()
+            .find_map(|bindings| action_for_key(bindings, &event))
+        {
+            event.prevent_default();
+            let _ = proxy.send_event(AppEvent::Action(action));
+        }
+    });
+    input
+        .add_event_listener_with_callback("keydown", on_keydown.as_ref().unchecked_ref())
+        .ok()?;
+    on_keydown.forget();
+
+    Some(input)
+}
+
+pub const MAX_DROP_BYTES: f64 = 4.0 * 1024.0 * 1024.0;
+
+pub fn install_drop_handler(
+    canvas: &HtmlCanvasElement,
+    proxy: EventLoopProxy<AppEvent>,
+) -> Option<()> {
+    let on_dragover = Closure::<dyn FnMut(DragEvent)>::new(|event: DragEvent| {
+        event.prevent_default();
+    });
+    canvas
+        .add_event_listener_with_callback("dragover", on_dragover.as_ref().unchecked_ref())
+        .ok()?;
+    on_dragover.forget();
+
+    let on_drop = Closure::<dyn FnMut(DragEvent)>::new(move |event: DragEvent| {
+        event.prevent_default();
+        let Some(file) = event
+            .data_transfer()
+            .and_then(|data| data.files())
+            .and_then(|files| files.get(0))
+        else {
+            return;
+        };
+
+        let proxy = proxy.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let _ = proxy.send_event(read_file(file, None).await);
+        });
+    });
+    canvas
+        .add_event_listener_with_callback("drop", on_drop.as_ref().unchecked_ref())
+        .ok()?;
+    on_drop.forget();
+
+    Some(())
+}
+
+/// Reads a dropped or picked file, along with the `handle` to write it back
+/// through if it was picked.
+pub async fn read_file(file: File, handle: Option<FileHandle>) -> AppEvent {
+    let name = file.name();
+    if file.size() > MAX_DROP_BYTES {
+        return AppEvent::Message(format!(
+            "{name} is too large to open (the limit is {} MiB)",
+            MAX_DROP_BYTES / (1024.0 * 1024.0)
+        ));
+    }
+
+    match JsFuture::from(file.text()).await {
+        Ok(contents) => AppEvent::FileLoaded {
+            name,
+            contents: contents.as_string().unwrap_or_default(),
+            handle,
+        },
+        Err(err) => AppEvent::Message(format!("Failed to read {name}: {err:?}")),
+    }
+}
+
+/// Reports the first key, click or scroll anywhere in the editor, which ends
+/// the demo. Each listener removes itself after it fires, so they don't
+/// outlive the demo by more than one event of each kind.
+pub fn install_interaction_handler(dst: &Element, proxy: EventLoopProxy<AppEvent>) -> Option<()> {
+    let on_interaction = Closure::<dyn FnMut()>::new(move || {
+        let _ = proxy.send_event(AppEvent::Interacted);
+    });
+    let options = AddEventListenerOptions::new();
+    options.set_once(true);
+    for name in ["keydown", "pointerdown", "wheel"] {
+        dst.add_event_listener_with_callback_and_add_event_listener_options(
+            name,
+            on_interaction.as_ref().unchecked_ref(),
+            &options,
+        )
+        .ok()?;
+    }
+    on_interaction.forget();
+
+    Some(())
+}
+
+pub fn install_visibility_handler(doc: &Document, proxy: EventLoopProxy<AppEvent>) -> Option<()> {
+    let target = doc.clone();
+    let on_change = Closure::<dyn FnMut()>::new(move || {
+        let _ = proxy.send_event(AppEvent::VisibilityChanged {
+            hidden: target.hidden(),
+        });
+    });
+    doc.add_event_listener_with_callback("visibilitychange", on_change.as_ref().unchecked_ref())
+        .ok()?;
+    on_change.forget();
+
+    Some(())
+}
+
+pub fn watch_media_query(
+    query: &str,
+    proxy: EventLoopProxy<AppEvent>,
+    to_event: fn(bool) -> AppEvent,
+) -> Option<bool> {
+    let list = web_sys::window()?.match_media(query).ok()??;
+
+    let on_change =
+        Closure::<dyn FnMut(MediaQueryListEvent)>::new(move |event: MediaQueryListEvent| {
+            let _ = proxy.send_event(to_event(event.matches()));
+        });
+    list.add_event_listener_with_callback("change", on_change.as_ref().unchecked_ref())
+        .ok()?;
+    on_change.forget();
+
+    Some(list.matches())
+}
+
+pub const MULTI_CLICK_MS: f64 = 400.0;
+
+/// The UTF-16 range of the word, whitespace run, or punctuation under `offset`.
+pub fn word_at(text: &str, line: &Row, offset: u32) -> Range<u32> {
+    let mut start = line.offset;
+    for word in text[line.bytes.clone()].split_word_bounds() {
+        let end = start + word.encode_utf16().count() as u32;
+        if offset < end {
+            return start..end;
+        }
+        start = end;
+    }
+
+    start..start
+}
+
+pub fn selection(input: &HtmlTextAreaElement) -> Range<u32> {
+    let start = input.selection_start().ok().flatten().unwrap_or_default();
+    let end = input.selection_end().ok().flatten().unwrap_or(start);
+    start..end
+}
+
+/// The byte index of the UTF-16 `offset` into `text`, clamped to its length.
+pub fn byte_at(text: &str, offset: u32) -> usize {
+    let mut unit = 0;
+    for (idx, c) in text.char_indices() {
+        if unit >= offset {
+            return idx;
+        }
+        unit += c.len_utf16() as u32;
+    }
+    text.len()
+}
+
+/// The text the textarea has selected.
+pub fn selected_text(input: &HtmlTextAreaElement) -> String {
+    let value = input.value();
+    let range = selection(input);
+    value[byte_at(&value, range.start)..byte_at(&value, range.end)].to_string()
+}
+
+/// Selects from `anchor` to `focus`, leaving the caret at `focus`.
+pub fn select(input: &HtmlTextAreaElement, anchor: u32, focus: u32) {
+    let direction = if focus < anchor {
+        "backward"
+    } else {
+        "forward"
+    };
+    let _ =
+        input.set_selection_range_with_direction(anchor.min(focus), anchor.max(focus), direction);
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+
+    fn rows(text: &str) -> Vec<Row> {
+        let mut cache = crate::render::RenderCache::default();
+        cache.index_lines(text);
+        cache.lines
+    }
+
+    #[wasm_bindgen_test]
+    fn keys_map_to_actions_by_their_modifiers() {
+        assert_eq!(
+            action_for(KEY_BINDINGS, "s", true, false, false),
+            Some(Action::Save)
+        );
+        assert_eq!(
+            action_for(EDITOR_BINDINGS, "z", true, false, false),
+            Some(Action::Undo)
+        );
+        // Shift turns the key the browser reports upper case.
+        assert_eq!(
+            action_for(EDITOR_BINDINGS, "Z", true, true, false),
+            Some(Action::Redo)
+        );
+        assert_eq!(
+            action_for(EDITOR_BINDINGS, "z", false, false, true),
+            Some(Action::ToggleWrap)
+        );
+        assert_eq!(
+            action_for(EDITOR_BINDINGS, "Home", true, true, false),
+            Some(Action::ExtendSelection(Motion::DocumentStart))
+        );
+        assert_eq!(
+            action_for(KEY_BINDINGS, "n", false, false, true),
+            Some(Action::NewDocument)
+        );
+        assert_eq!(action_for(KEY_BINDINGS, "n", true, false, false), None);
+        assert_eq!(action_for(EDITOR_BINDINGS, "z", false, false, false), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn no_binding_is_shadowed() {
+        let editor = EDITOR_BINDINGS.iter().chain(KEY_BINDINGS);
+        for bindings in [
+            editor.collect::<Vec<_>>(),
+            FIND_BINDINGS.iter().collect(),
+            PALETTE_BINDINGS.iter().collect(),
+            GO_TO_LINE_BINDINGS.iter().collect(),
+            CONFIRM_BINDINGS.iter().collect(),
+            REPLACE_BINDINGS.iter().collect(),
+        ] {
+            for (idx, binding) in bindings.iter().enumerate() {
+                let first = bindings
+                    .iter()
+                    .position(|other| {
+                        other.key.eq_ignore_ascii_case(binding.key)
+                            && (other.ctrl, other.shift, other.alt)
+                                == (binding.ctrl, binding.shift, binding.alt)
+                    })
+                    .unwrap();
+                assert_eq!(first, idx, "{} is bound twice", binding.label());
+            }
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn labels_name_the_modifiers() {
+        let binding = EDITOR_BINDINGS
+            .iter()
+            .find(|binding| binding.action == Action::Redo)
+            .unwrap();
+        assert_eq!(binding.label(), "Ctrl+Shift+Z");
+        assert_eq!(shortcut_for(Action::Save).as_deref(), Some("Ctrl+S"));
+    }
+
+    #[wasm_bindgen_test]
+    fn utf16_offsets_map_to_byte_indexes() {
+        // "é" is two bytes and one unit, "😊" four bytes and two units.
+        let text = "aé😊b";
+        assert_eq!(byte_at(text, 0), 0);
+        assert_eq!(byte_at(text, 1), 1);
+        assert_eq!(byte_at(text, 2), 3);
+        assert_eq!(byte_at(text, 4), 7);
+        assert_eq!(byte_at(text, 5), 8);
+        assert_eq!(byte_at(text, 100), text.len());
+    }
+
+    #[wasm_bindgen_test]
+    fn words_are_found_by_utf16_offset() {
+        let text = "one 😊😊 two";
+        let rows = rows(text);
+        assert_eq!(word_at(text, &rows[0], 1), 0..3);
+        assert_eq!(word_at(text, &rows[0], 3), 3..4);
+        assert_eq!(word_at(text, &rows[0], 9), 9..12);
+        // Past the end of the line there is no word.
+        assert_eq!(word_at(text, &rows[0], 12), 12..12);
+    }
+}
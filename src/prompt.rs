@@ -0,0 +1,619 @@
+use ratatui::{
+    prelude::*,
+    symbols::border,
+    widgets::{
+        Block,
+        Clear,
+        Paragraph,
+    },
+};
+
+use crate::input::{
+    Action,
+    CONFIRM_BINDINGS,
+    EDITOR_BINDINGS,
+    FIND_BINDINGS,
+    GO_TO_LINE_BINDINGS,
+    KEY_BINDINGS,
+    KeyBinding,
+    Motion,
+    PALETTE_BINDINGS,
+    REPLACE_BINDINGS,
+    shortcut_for,
+};
+
+pub struct Command {
+    pub name: &'static str,
+    pub action: Action,
+}
+
+/// The commands listed in the palette.
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: "Find",
+        action: Action::Find,
+    },
+    Command {
+        name: "Replace",
+        action: Action::OpenReplace,
+    },
+    Command {
+        name: "Go to line",
+        action: Action::OpenGoToLine,
+    },
+    Command {
+        name: "New document",
+        action: Action::NewDocument,
+    },
+    Command {
+        name: "Reset to the sample text",
+        action: Action::ResetToSample,
+    },
+    Command {
+        name: "Open file",
+        action: Action::OpenFile,
+    },
+    Command {
+        name: "Show unsaved changes",
+        action: Action::ToggleDiff,
+    },
+    Command {
+        name: "Save",
+        action: Action::Save,
+    },
+    Command {
+        name: "Copy selection",
+        action: Action::Copy,
+    },
+    Command {
+        name: "Undo",
+        action: Action::Undo,
+    },
+    Command {
+        name: "Redo",
+        action: Action::Redo,
+    },
+    Command {
+        name: "Sort lines",
+        action: Action::SortLines,
+    },
+    Command {
+        name: "Remove duplicate lines",
+        action: Action::UniqueLines,
+    },
+    Command {
+        name: "Uppercase selection",
+        action: Action::Uppercase,
+    },
+    Command {
+        name: "Lowercase selection",
+        action: Action::Lowercase,
+    },
+    Command {
+        name: "Title case selection",
+        action: Action::TitleCase,
+    },
+    Command {
+        name: "Base64 encode selection",
+        action: Action::Base64Encode,
+    },
+    Command {
+        name: "Base64 decode selection",
+        action: Action::Base64Decode,
+    },
+    Command {
+        name: "URL encode selection",
+        action: Action::UrlEncode,
+    },
+    Command {
+        name: "URL decode selection",
+        action: Action::UrlDecode,
+    },
+    Command {
+        name: "Prettify JSON",
+        action: Action::PrettifyJson,
+    },
+    Command {
+        name: "Minify JSON",
+        action: Action::MinifyJson,
+    },
+    Command {
+        name: "Use LF line endings",
+        action: Action::UseLf,
+    },
+    Command {
+        name: "Use CRLF line endings",
+        action: Action::UseCrlf,
+    },
+    Command {
+        name: "Toggle word wrap",
+        action: Action::ToggleWrap,
+    },
+    Command {
+        name: "Toggle high contrast",
+        action: Action::ToggleHighContrast,
+    },
+    Command {
+        name: "Next theme",
+        action: Action::CycleTheme,
+    },
+    Command {
+        name: "Toggle typewriter scrolling",
+        action: Action::ToggleTypewriter,
+    },
+    Command {
+        name: "Toggle whitespace",
+        action: Action::ToggleWhitespace,
+    },
+    Command {
+        name: "Toggle trailing whitespace",
+        action: Action::ToggleTrailingWhitespace,
+    },
+    Command {
+        name: "Toggle ruler",
+        action: Action::ToggleRuler,
+    },
+    Command {
+        name: "Toggle line numbers",
+        action: Action::ToggleLineNumbers,
+    },
+    Command {
+        name: "Toggle line comment",
+        action: Action::ToggleComment,
+    },
+    Command {
+        name: "Move lines up",
+        action: Action::MoveLinesUp,
+    },
+    Command {
+        name: "Move lines down",
+        action: Action::MoveLinesDown,
+    },
+    Command {
+        name: "Duplicate lines",
+        action: Action::DuplicateLines,
+    },
+    Command {
+        name: "Toggle auto-close brackets",
+        action: Action::ToggleAutoClose,
+    },
+    Command {
+        name: "Next line spacing",
+        action: Action::CycleLineSpacing,
+    },
+    Command {
+        name: "Toggle minimap",
+        action: Action::ToggleMinimap,
+    },
+    Command {
+        name: "Split editor side by side",
+        action: Action::SplitSideBySide,
+    },
+    Command {
+        name: "Split editor top and bottom",
+        action: Action::SplitStacked,
+    },
+    Command {
+        name: "Close split",
+        action: Action::CloseSplit,
+    },
+    Command {
+        name: "Switch pane",
+        action: Action::SwitchPane,
+    },
+    Command {
+        name: "Toggle CRT effect",
+        action: Action::ToggleCrt,
+    },
+    Command {
+        name: "Next post-processing effect",
+        action: Action::CycleEffect,
+    },
+    Command {
+        name: "Strengthen CRT effect",
+        action: Action::StrengthenCrt,
+    },
+    Command {
+        name: "Weaken CRT effect",
+        action: Action::WeakenCrt,
+    },
+    Command {
+        name: "Increase brightness",
+        action: Action::Brighten,
+    },
+    Command {
+        name: "Decrease brightness",
+        action: Action::Dim,
+    },
+    Command {
+        name: "Increase contrast",
+        action: Action::IncreaseContrast,
+    },
+    Command {
+        name: "Decrease contrast",
+        action: Action::DecreaseContrast,
+    },
+    Command {
+        name: "Reset brightness and contrast",
+        action: Action::ResetAdjustment,
+    },
+    Command {
+        name: "Keyboard shortcuts",
+        action: Action::ToggleHelp,
+    },
+    Command {
+        name: "Toggle frame stats",
+        action: Action::ToggleFrameStats,
+    },
+    Command {
+        name: "Toggle fullscreen",
+        action: Action::ToggleFullscreen,
+    },
+    Command {
+        name: "Next font",
+        action: Action::CycleFont,
+    },
+    Command {
+        name: "Zoom in",
+        action: Action::ZoomIn,
+    },
+    Command {
+        name: "Zoom out",
+        action: Action::ZoomOut,
+    },
+    Command {
+        name: "Reset zoom",
+        action: Action::ZoomReset,
+    },
+];
+
+/// Whether the letters of `filter` appear in order in `name`, ignoring case.
+pub fn fuzzy_match(name: &str, filter: &str) -> bool {
+    let mut name = name.chars().flat_map(char::to_lowercase);
+    filter
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|c| name.any(|candidate| candidate == c))
+}
+
+pub const PALETTE_WIDTH: u16 = 60;
+pub const PALETTE_ROWS: u16 = 12;
+
+#[derive(Default)]
+pub struct Palette {
+    pub open: bool,
+    pub filter: String,
+    /// The index of the highlighted command among the filtered ones.
+    pub selected: usize,
+}
+
+impl Palette {
+    pub fn entries(&self) -> impl Iterator<Item = &'static Command> {
+        COMMANDS
+            .iter()
+            .filter(|command| fuzzy_match(command.name, &self.filter))
+    }
+
+    pub fn step(&mut self, forward: bool) {
+        let len = self.entries().count();
+        if len > 0 {
+            self.selected = if forward {
+                (self.selected + 1) % len
+            } else {
+                (self.selected + len - 1) % len
+            };
+        }
+    }
+
+    pub fn selected(&self) -> Option<&'static Command> {
+        self.entries().nth(self.selected)
+    }
+}
+
+impl Widget for &Palette {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let entries = self.entries().collect::<Vec<_>>();
+        let width = PALETTE_WIDTH.min(area.width.saturating_sub(4));
+        let height = (entries.len().max(1) as u16 + 3).min(PALETTE_ROWS);
+        let area = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + 1,
+            width,
+            height,
+        }
+        .intersection(area);
+
+        Clear.render(area, buf);
+        let block = Block::bordered()
+            .border_set(border::ROUNDED)
+            .title(" Commands ");
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let mut lines = vec![Line::from(format!("> {}▏", self.filter))];
+        if entries.is_empty() {
+            lines.push(Line::from("No matching commands").dim());
+        }
+
+        // Keeps the highlighted command in view when there are more than fit.
+        let visible = inner.height.saturating_sub(1) as usize;
+        let first = (self.selected + 1).saturating_sub(visible);
+        for (idx, command) in entries.iter().enumerate().skip(first).take(visible) {
+            let shortcut = shortcut_for(command.action).unwrap_or_default();
+            let gap = (inner.width as usize).saturating_sub(command.name.len() + shortcut.len());
+            let line = Line::from(vec![
+                Span::raw(command.name),
+                Span::raw(" ".repeat(gap)),
+                Span::raw(shortcut).dim(),
+            ]);
+            lines.push(if idx == self.selected {
+                line.reversed()
+            } else {
+                line
+            });
+        }
+
+        Paragraph::new(lines).render(inner, buf);
+    }
+}
+
+#[derive(Default)]
+pub struct GoToLine {
+    pub open: bool,
+    pub input: String,
+}
+
+impl GoToLine {
+    /// The requested line, counting from zero and clamped to the document.
+    pub fn target(&self, line_count: usize) -> Option<usize> {
+        let line = self.input.trim().parse::<usize>().ok()?;
+        Some(line.clamp(1, line_count.max(1)) - 1)
+    }
+}
+
+/// Asks before an action that would lose work. Typing "y" or "n" answers as
+/// well as the keys in [`CONFIRM_BINDINGS`].
+#[derive(Default)]
+pub struct Confirm {
+    /// The action waiting on an answer, while the prompt is open.
+    pub pending: Option<Action>,
+}
+
+impl Confirm {
+    pub fn prompt(&self) -> Option<PromptOverlay<'static>> {
+        let (title, question) = match self.pending? {
+            Action::NewDocument => (" New document ", "Discard the current text?"),
+            Action::ResetToSample => (" Reset ", "Replace the text with the sample?"),
+            _ => (" Confirm ", "Are you sure?"),
+        };
+        Some(PromptOverlay {
+            title,
+            label: format!("{question} (y/n)"),
+        })
+    }
+}
+
+/// A one line prompt drawn over the top of the editor.
+pub struct PromptOverlay<'a> {
+    pub title: &'a str,
+    pub label: String,
+}
+
+impl Widget for PromptOverlay<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let width = PALETTE_WIDTH.min(area.width.saturating_sub(4));
+        let area = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + 1,
+            width,
+            height: 3,
+        }
+        .intersection(area);
+
+        Clear.render(area, buf);
+        let block = Block::bordered()
+            .border_set(border::ROUNDED)
+            .title(self.title);
+        let inner = block.inner(area);
+        block.render(area, buf);
+        Paragraph::new(self.label).render(inner, buf);
+    }
+}
+
+/// The UTF-16 offset of the start of line `line`, counting from zero.
+pub fn line_start(text: &str, line: usize) -> u32 {
+    text.split('\n')
+        .take(line)
+        .map(|line| line.encode_utf16().count() as u32 + 1)
+        .sum()
+}
+
+/// What an action does, for the help overlay. Palette commands are described
+/// by their name there.
+pub fn describe(action: Action) -> &'static str {
+    if let Some(command) = COMMANDS.iter().find(|command| command.action == action) {
+        return command.name;
+    }
+    match action {
+        Action::FindNext => "Find next",
+        Action::FindPrevious => "Find previous",
+        Action::ToggleFindCase => "Toggle case sensitivity",
+        Action::ToggleFindRegex => "Toggle regex",
+        Action::CloseFind => "Close find",
+        Action::SwitchFindField => "Switch between find and replace",
+        Action::ReplaceNext => "Replace next",
+        Action::ReplaceAll => "Replace all",
+        Action::OpenPalette => "Open the command palette",
+        Action::PaletteUp => "Previous command",
+        Action::PaletteDown => "Next command",
+        Action::RunPaletteCommand => "Run command",
+        Action::ClosePalette => "Close the palette",
+        Action::ConfirmGoToLine => "Go to the line",
+        Action::CloseGoToLine => "Close go to line",
+        Action::ToggleHelp => "Show or hide this help",
+        Action::CloseHelp => "Close this help",
+        Action::Newline => "New line, keeping the indentation",
+        Action::Confirm => "Go ahead",
+        Action::CancelConfirm => "Cancel",
+        Action::MoveCaret(Motion::PageUp) => "Page up",
+        Action::MoveCaret(Motion::PageDown) => "Page down",
+        Action::MoveCaret(Motion::LineStart) => "Start of the line",
+        Action::MoveCaret(Motion::LineEnd) => "End of the line",
+        Action::MoveCaret(Motion::DocumentStart) => "Start of the document",
+        Action::MoveCaret(Motion::DocumentEnd) => "End of the document",
+        Action::ExtendSelection(Motion::PageUp) => "Select a page up",
+        Action::ExtendSelection(Motion::PageDown) => "Select a page down",
+        Action::ExtendSelection(Motion::LineStart) => "Select to the start of the line",
+        Action::ExtendSelection(Motion::LineEnd) => "Select to the end of the line",
+        Action::ExtendSelection(Motion::DocumentStart) => "Select to the start of the document",
+        Action::ExtendSelection(Motion::DocumentEnd) => "Select to the end of the document",
+        _ => "",
+    }
+}
+
+/// The tables listed in the help overlay, under these headings.
+pub const HELP_SECTIONS: &[(&str, &[KeyBinding])] = &[
+    ("Editor", EDITOR_BINDINGS),
+    ("Everywhere", KEY_BINDINGS),
+    ("Find", FIND_BINDINGS),
+    ("Replace", REPLACE_BINDINGS),
+    ("Command palette", PALETTE_BINDINGS),
+    ("Go to line", GO_TO_LINE_BINDINGS),
+    ("Confirmation", CONFIRM_BINDINGS),
+];
+
+#[derive(Default)]
+pub struct Help {
+    pub open: bool,
+}
+
+impl Widget for &Help {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let mut entries = vec![];
+        for (idx, (heading, bindings)) in HELP_SECTIONS.iter().enumerate() {
+            if idx > 0 {
+                entries.push((String::new(), ""));
+            }
+            entries.push((heading.to_string(), ""));
+            entries.extend(
+                bindings
+                    .iter()
+                    .map(|binding| (binding.label(), describe(binding.action))),
+            );
+        }
+
+        let key_width = entries.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+        let lines = entries
+            .iter()
+            .map(|(key, description)| {
+                if description.is_empty() {
+                    Line::from(key.as_str()).bold()
+                } else {
+                    Line::from(vec![
+                        Span::raw(format!("  {key:<key_width$}  ")),
+                        Span::raw(*description).dim(),
+                    ])
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // Flows into as many columns as it takes to fit on screen.
+        let column_width = lines.iter().map(Line::width).max().unwrap_or(0) as u16 + 2;
+        let rows = area.height.saturating_sub(4).max(1);
+        let columns = (lines.len() as u16).div_ceil(rows);
+        let width = (column_width * columns + 2).min(area.width);
+        let height = (lines.len() as u16).min(rows) + 2;
+        let area = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height.saturating_sub(height)) / 2,
+            width,
+            height,
+        }
+        .intersection(area);
+
+        Clear.render(area, buf);
+        let block = Block::bordered()
+            .border_set(border::ROUNDED)
+            .title(" Keyboard shortcuts ")
+            .title_bottom(" Esc to close ");
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        for (column, lines) in lines.chunks(rows as usize).enumerate() {
+            let column = Rect {
+                x: inner.x + column as u16 * column_width,
+                width: column_width,
+                ..inner
+            }
+            .intersection(inner);
+            Paragraph::new(lines.to_vec()).render(column, buf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+
+    #[wasm_bindgen_test]
+    fn filters_match_letters_in_order_ignoring_case() {
+        assert!(fuzzy_match("Go to line", "gtl"));
+        assert!(fuzzy_match("Go to line", "GOTO"));
+        assert!(fuzzy_match("Go to line", ""));
+        assert!(!fuzzy_match("Go to line", "lg"));
+        assert!(!fuzzy_match("Go to line", "gotoo"));
+    }
+
+    #[wasm_bindgen_test]
+    fn palette_selection_wraps_through_the_filtered_commands() {
+        let mut palette = Palette {
+            filter: "zoom".to_string(),
+            ..Palette::default()
+        };
+        let names = palette
+            .entries()
+            .map(|command| command.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Zoom in", "Zoom out", "Reset zoom"]);
+
+        palette.step(false);
+        assert_eq!(
+            palette.selected().map(|command| command.name),
+            Some("Reset zoom")
+        );
+        palette.step(true);
+        assert_eq!(
+            palette.selected().map(|command| command.name),
+            Some("Zoom in")
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn line_starts_are_utf16_offsets() {
+        let text = "ab\n😊c\nd";
+        assert_eq!(line_start(text, 0), 0);
+        assert_eq!(line_start(text, 1), 3);
+        assert_eq!(line_start(text, 2), 7);
+    }
+
+    #[wasm_bindgen_test]
+    fn go_to_line_targets_are_clamped_to_the_document() {
+        let target = |input: &str, line_count| {
+            GoToLine {
+                open: true,
+                input: input.to_string(),
+            }
+            .target(line_count)
+        };
+        assert_eq!(target("3", 10), Some(2));
+        assert_eq!(target(" 4 ", 10), Some(3));
+        assert_eq!(target("0", 10), Some(0));
+        assert_eq!(target("99", 10), Some(9));
+        assert_eq!(target("5", 0), Some(0));
+        assert_eq!(target("", 10), None);
+        assert_eq!(target("-1", 10), None);
+        assert_eq!(target("x", 10), None);
+    }
+}
@@ -0,0 +1,1923 @@
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    hash::{
+        DefaultHasher,
+        Hash,
+        Hasher,
+    },
+    ops::Range,
+};
+
+use ratatui::{
+    prelude::*,
+    symbols::border,
+    widgets::{
+        Block,
+        Clear,
+        Paragraph,
+        Scrollbar,
+        ScrollbarOrientation,
+        ScrollbarState,
+    },
+};
+use similar::{
+    ChangeTag,
+    TextDiff,
+};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
+
+use crate::{
+    config::{
+        CaretStyle,
+        Config,
+        Theme,
+    },
+    edit::{
+        BracketMatch,
+        match_bracket,
+    },
+    find::{
+        FindBar,
+        in_ranges,
+    },
+    input::byte_at,
+    prompt::{
+        Confirm,
+        GoToLine,
+        Help,
+        Palette,
+        PromptOverlay,
+    },
+    storage::SaveState,
+    syntax::{
+        Highlighter,
+        Language,
+        PlainText,
+        Token,
+        token_style,
+    },
+};
+
+pub const MESSAGE_DURATION_MS: f64 = 4000.0;
+
+pub struct StatusMessage {
+    pub text: String,
+    pub expires_at: f64,
+}
+
+#[derive(Default)]
+pub struct ViewState {
+    /// The second pane is only shown while the editor is split.
+    pub panes: [Pane; 2],
+    /// Which way the panes are laid out, or `None` for a single pane.
+    pub split: Option<Direction>,
+    /// The pane the caret is followed in and whose scrolling the keyboard and
+    /// minimap drive.
+    pub active: usize,
+    /// Turns off scroll animation, following `prefers-reduced-motion`.
+    pub reduced_motion: bool,
+    /// Where the minimap was last drawn, if it is shown.
+    pub minimap_area: Option<Rect>,
+    /// Where the unsaved changes are scrolled to, while they are shown.
+    pub diff_scroll: Option<usize>,
+    pub message: Option<StatusMessage>,
+}
+
+impl ViewState {
+    pub fn pane(&self) -> &Pane {
+        &self.panes[self.active]
+    }
+
+    pub fn pane_mut(&mut self) -> &mut Pane {
+        &mut self.panes[self.active]
+    }
+
+    /// The panes on screen.
+    pub fn shown(&self) -> &[Pane] {
+        let count = if self.split.is_some() { 2 } else { 1 };
+        &self.panes[..count]
+    }
+
+    /// The index of the pane drawn over `cell`, if any.
+    pub fn pane_at(&self, cell: Position) -> Option<usize> {
+        self.shown()
+            .iter()
+            .position(|pane| pane.area.contains(cell))
+    }
+
+    /// The index of the pane whose scrollbar is drawn over `cell`, if any.
+    pub fn scrollbar_at(&self, cell: Position) -> Option<usize> {
+        self.shown()
+            .iter()
+            .position(|pane| pane.scrollbar.is_some_and(|track| track.contains(cell)))
+    }
+
+    /// Splits the editor, with the new pane starting where the active one is.
+    pub fn split(&mut self, direction: Direction) {
+        if self.split.is_none() {
+            let pane = self.pane();
+            self.panes[1] = Pane {
+                scroll_row: pane.scroll_row,
+                scroll_col: pane.scroll_col,
+                shown_row: pane.shown_row,
+                ..Pane::default()
+            };
+        }
+        self.split = Some(direction);
+    }
+
+    pub fn unsplit(&mut self) {
+        self.split = None;
+        self.active = 0;
+    }
+
+    /// Whether a scroll is still animating in any pane, and so needs more
+    /// frames.
+    pub fn scrolling(&self) -> bool {
+        self.shown().iter().any(Pane::scrolling)
+    }
+
+    /// Shows `text` in the status bar from `now` until it expires.
+    pub fn notify(&mut self, text: impl Into<String>, now: f64) {
+        self.message = Some(StatusMessage {
+            text: text.into(),
+            expires_at: now + MESSAGE_DURATION_MS,
+        });
+    }
+}
+
+/// One view onto the document, scrolled independently of any other.
+#[derive(Default)]
+pub struct Pane {
+    pub scroll_row: usize,
+    pub scroll_col: usize,
+    /// Set when the text or selection changes so the next render scrolls the
+    /// caret into view. Otherwise the scroll position is left where the user
+    /// put it.
+    pub follow_caret: bool,
+    /// The row drawn at the top while a scroll animates toward `scroll_row`.
+    pub shown_row: f64,
+    /// The whole pane, gutter included.
+    pub area: Rect,
+    pub text_area: Rect,
+    /// The blank rows drawn after each row of text.
+    pub spacing: usize,
+    /// The wrapped layout from the last render, or empty when wrapping is off.
+    pub wrapped: Vec<Row>,
+    /// Where the scrollbar was last drawn, or `None` when the document fit.
+    pub scrollbar: Option<Rect>,
+    /// The spans of the last row drawn, kept so each row is highlighted into
+    /// the same allocation. It's emptied before being put back, so it never
+    /// borrows the text between frames.
+    pub spans: Vec<Span<'static>>,
+}
+
+/// The fraction of the remaining distance a smooth scroll covers each frame.
+pub const SCROLL_EASING: f64 = 0.35;
+
+impl Pane {
+    /// The rows the pane was last laid out in: its wrapped rows, or the lines
+    /// themselves without wrapping.
+    pub fn rows<'a>(&'a self, lines: &'a [Row]) -> &'a [Row] {
+        if self.wrapped.is_empty() {
+            lines
+        } else {
+            &self.wrapped
+        }
+    }
+
+    /// Wraps `lines` again at the width of the last render, for when the text
+    /// has changed since. Does nothing when wrapping is off.
+    pub fn rewrap(&mut self, text: &str, lines: &[Row], widths: CellWidths) {
+        if self.wrapped.is_empty() {
+            return;
+        }
+        self.wrapped.clear();
+        for line in lines {
+            wrap_line(
+                text,
+                line,
+                self.text_area.width as usize,
+                widths,
+                &mut self.wrapped,
+            );
+        }
+    }
+
+    /// How many rows of text fit in the pane.
+    pub fn visible_rows(&self) -> usize {
+        (self.text_area.height as usize + self.spacing) / (self.spacing + 1)
+    }
+
+    /// Maps a cell on screen back to the UTF-16 offset of the grapheme under
+    /// it, using the rows laid out by the last render. Positions past the
+    /// end of a row snap to its end.
+    pub fn offset_at(&self, text: &str, lines: &[Row], cell: Position, widths: CellWidths) -> u32 {
+        let rows = self.rows(lines);
+        let Some(last) = rows.len().checked_sub(1) else {
+            return 0;
+        };
+
+        let row =
+            self.top_row() + cell.y.saturating_sub(self.text_area.y) as usize / (self.spacing + 1);
+        let row = &rows[row.min(last)];
+        let column = self.scroll_col + cell.x.saturating_sub(self.text_area.x) as usize;
+        offset_in_row(text, row, column, widths)
+    }
+
+    /// The first row on screen, which trails `scroll_row` while scrolling is
+    /// animated.
+    pub fn top_row(&self) -> usize {
+        self.shown_row.round() as usize
+    }
+
+    /// Whether a scroll is still animating, and so needs more frames.
+    pub fn scrolling(&self) -> bool {
+        self.shown_row != self.scroll_row as f64
+    }
+
+    /// Moves the shown row toward `scroll_row`. Rows are whole cells, so the
+    /// animation eases out in steps and snaps once it's within half a row.
+    fn step_scroll(&mut self, smooth: bool, max_row: usize) -> usize {
+        let target = self.scroll_row as f64;
+        let shown = self.shown_row.min(max_row as f64);
+        self.shown_row = if smooth && (target - shown).abs() > 0.5 {
+            shown + (target - shown) * SCROLL_EASING
+        } else {
+            target
+        };
+        self.top_row()
+    }
+
+    /// Scrolls so the scrollbar's thumb is at row `y` of the screen, without
+    /// animating, since the thumb is being dragged directly.
+    pub fn scroll_to_scrollbar(&mut self, lines: &[Row], y: u16) {
+        let Some(track) = self.scrollbar else {
+            return;
+        };
+        let max_row = self.rows(lines).len().saturating_sub(self.visible_rows());
+        let along = y
+            .saturating_sub(track.y)
+            .min(track.height.saturating_sub(1)) as usize;
+        let span = (track.height as usize).saturating_sub(1).max(1);
+        self.scroll_row = (along * max_row + span / 2) / span;
+        self.shown_row = self.scroll_row as f64;
+        self.follow_caret = false;
+    }
+
+    /// Scrolls by a number of rows and columns. The result is clamped to the
+    /// document when it is next rendered.
+    pub fn scroll_by(&mut self, rows: isize, cols: isize) {
+        self.scroll_row = self.scroll_row.saturating_add_signed(rows);
+        self.scroll_col = self.scroll_col.saturating_add_signed(cols);
+    }
+}
+
+/// The UTF-16 offset of the grapheme drawn at `column` of `row`, or of the
+/// row's end if it is narrower.
+pub fn offset_in_row(text: &str, row: &Row, column: usize, widths: CellWidths) -> u32 {
+    let mut offset = row.offset;
+    let mut x = row.indent;
+    for grapheme in text[row.bytes.clone()].graphemes(true) {
+        let width = cell_width(grapheme, x, widths);
+        if column < x + width {
+            break;
+        }
+        x += width;
+        offset += grapheme.encode_utf16().count() as u32;
+    }
+
+    offset
+}
+
+/// The UTF-16 offset the caret goes to at the end of `row`. A row that wraps
+/// ends where the next one starts, so its caret stops before the last
+/// grapheme to stay on the row.
+pub fn row_end(text: &str, row: &Row) -> u32 {
+    let row_text = &text[row.bytes.clone()];
+    let end = row.offset + row_text.encode_utf16().count() as u32;
+    match row_text.graphemes(true).next_back() {
+        Some(grapheme) if !row.last => end - grapheme.encode_utf16().count() as u32,
+        _ => end,
+    }
+}
+
+pub fn scroll_into_view(scroll: usize, target: usize, height: usize, len: usize) -> usize {
+    let scroll = if target < scroll {
+        target
+    } else if height > 0 && target >= scroll + height {
+        target + 1 - height
+    } else {
+        scroll
+    };
+
+    scroll.min(len.saturating_sub(height))
+}
+
+/// A run of the document displayed on one row. Without wrapping every line is a
+/// single row; with wrapping a line may be split over several, the later ones
+/// prefixed by `indent` cells.
+#[derive(Debug, Clone)]
+pub struct Row {
+    pub line: usize,
+    pub bytes: Range<usize>,
+    pub offset: u32,
+    pub indent: usize,
+    pub last: bool,
+}
+
+#[derive(Default)]
+pub struct RenderCache {
+    pub lines: Vec<Row>,
+    pub last_frame: Option<u64>,
+    pub tokens: Vec<Token>,
+    /// A hash of the text and language `tokens` were produced from, so they are
+    /// only rebuilt when the document changes.
+    pub tokens_for: Option<u64>,
+    pub counts: Counts,
+    /// A hash of the text and selection `counts` were taken from.
+    pub counts_for: Option<u64>,
+    /// The unsaved changes as drawn by [`DiffView`].
+    pub changes: Vec<Line<'static>>,
+    /// A hash of the saved text, document, tab width and theme `changes` were
+    /// made from, so the diff is only redone when one of them changes.
+    pub changes_for: Option<u64>,
+}
+
+/// The size of the document, or of the selection when there is one.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Counts {
+    pub lines: usize,
+    pub words: usize,
+    /// Grapheme clusters, so "👨‍👩‍👧" counts once.
+    pub chars: usize,
+    pub selected: bool,
+}
+
+impl Counts {
+    pub fn of(text: &str, selected: bool) -> Self {
+        Self {
+            lines: text.split('\n').count(),
+            words: text.unicode_words().count(),
+            chars: text.graphemes(true).count(),
+            selected,
+        }
+    }
+}
+
+impl RenderCache {
+    pub fn tokenize(&mut self, text: &str, language: Option<Language>) {
+        let mut hasher = DefaultHasher::new();
+        (text, language).hash(&mut hasher);
+        let key = hasher.finish();
+        if self.tokens_for == Some(key) {
+            return;
+        }
+
+        self.tokens_for = Some(key);
+        let highlighter = language.map_or(&PlainText as &dyn Highlighter, Language::highlighter);
+        self.tokens = highlighter.highlight(text);
+    }
+
+    pub fn count(&mut self, text: &str, selection: &Range<u32>) {
+        let mut hasher = DefaultHasher::new();
+        (text, selection).hash(&mut hasher);
+        let key = hasher.finish();
+        if self.counts_for == Some(key) {
+            return;
+        }
+
+        self.counts_for = Some(key);
+        self.counts = if selection.is_empty() {
+            Counts::of(text, false)
+        } else {
+            let bytes = byte_at(text, selection.start)..byte_at(text, selection.end);
+            Counts::of(&text[bytes], true)
+        };
+    }
+
+    pub fn diff(&mut self, saved: &str, text: &str, tab_width: usize, theme: Theme) {
+        let mut hasher = DefaultHasher::new();
+        (saved, text, tab_width, theme).hash(&mut hasher);
+        let key = hasher.finish();
+        if self.changes_for == Some(key) {
+            return;
+        }
+
+        self.changes_for = Some(key);
+        self.changes = diff_lines(saved, text, tab_width, theme);
+    }
+
+    pub fn index_lines(&mut self, text: &str) {
+        self.lines.clear();
+
+        // Selection bounds from the textarea are UTF-16 code unit offsets, so lines are
+        // indexed by the same measure.
+        let mut byte = 0;
+        let mut offset = 0;
+        for (idx, line) in text.split('\n').enumerate() {
+            self.lines.push(Row {
+                line: idx,
+                bytes: byte..byte + line.len(),
+                offset,
+                indent: 0,
+                last: true,
+            });
+            byte += line.len() + 1;
+            offset += line.encode_utf16().count() as u32 + 1;
+        }
+    }
+}
+
+/// How many frames the FPS overlay averages over.
+pub const FRAME_SAMPLES: usize = 60;
+/// How often the FPS overlay's numbers change, so they stay readable and the
+/// overlay doesn't force a new frame every tick.
+pub const FRAME_STATS_REFRESH_MS: f64 = 500.0;
+
+/// Timings for the most recently rendered frames.
+#[derive(Default)]
+pub struct FrameStats {
+    pub visible: bool,
+    /// When each frame started, from `performance.now()`.
+    pub starts: VecDeque<f64>,
+    /// How long each frame took to draw and present, in milliseconds.
+    pub durations: VecDeque<f64>,
+    pub refresh_at: f64,
+}
+
+impl FrameStats {
+    pub fn record(&mut self, start: f64, end: f64) {
+        if self.starts.len() == FRAME_SAMPLES {
+            self.starts.pop_front();
+            self.durations.pop_front();
+        }
+        self.starts.push_back(start);
+        self.durations.push_back(end - start);
+    }
+
+    pub fn fps(&self) -> f64 {
+        match (self.starts.front(), self.starts.back()) {
+            (Some(first), Some(last)) if last > first => {
+                (self.starts.len() - 1) as f64 * 1000.0 / (last - first)
+            }
+            _ => 0.0,
+        }
+    }
+
+    pub fn average_ms(&self) -> f64 {
+        if self.durations.is_empty() {
+            return 0.0;
+        }
+        self.durations.iter().sum::<f64>() / self.durations.len() as f64
+    }
+
+    pub fn label(&self) -> String {
+        format!(" {:.0} fps | {:.2} ms ", self.fps(), self.average_ms())
+    }
+}
+
+pub const WHITESPACE_STYLE: Style = Style::new().fg(Color::DarkGray);
+pub const CONTROL_STYLE: Style = Style::new().add_modifier(Modifier::DIM);
+
+pub const MAX_TAB_WIDTH: usize = 16;
+pub const TAB_SPACES: &str = "                ";
+
+pub const EMOJI_PRESENTATION: char = '\u{FE0F}';
+
+/// The Unicode control picture drawn in place of a C0 control character or
+/// DEL, like "␛" for ESC. Each is a single grapheme one cell wide.
+pub fn control_picture(grapheme: &str) -> Option<char> {
+    let mut chars = grapheme.chars();
+    let c = chars.next()?;
+    if !chars.as_str().is_empty() {
+        return None;
+    }
+    match c {
+        '\0'..='\u{1F}' => char::from_u32(0x2400 + c as u32),
+        '\u{7F}' => Some('\u{2421}'),
+        _ => None,
+    }
+}
+
+/// What decides how many cells text is laid out in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellWidths {
+    pub tab_width: usize,
+    /// Lays out East Asian ambiguous-width characters, like "±" and "Ω", as two
+    /// cells, as CJK fonts and terminals usually draw them.
+    pub ambiguous_wide: bool,
+}
+
+/// The number of cells a grapheme cluster occupies. A cluster is drawn as a
+/// single glyph, so summing its code points overcounts sequences like
+/// "👨‍👩‍👧". Instead the cluster takes the width of its base character, and
+/// the marks after it add nothing.
+///
+/// Clusters drawn as emoji are two cells even when their base is narrow on its
+/// own: VS16 sequences like "❤️" and keycaps like "#️⃣", and flags made from a
+/// pair of regional indicators.
+pub fn grapheme_width(grapheme: &str, ambiguous_wide: bool) -> usize {
+    let mut chars = grapheme.chars();
+    let Some(base) = chars.next() else {
+        return 1;
+    };
+    // Drawn as control pictures, which are narrow.
+    if base.is_ascii_control() {
+        return 1;
+    }
+
+    let regional_indicator = ('\u{1F1E6}'..='\u{1F1FF}').contains(&base);
+    if chars.as_str().contains(EMOJI_PRESENTATION)
+        || (regional_indicator && !chars.as_str().is_empty())
+    {
+        return 2;
+    }
+
+    let width = if ambiguous_wide {
+        base.width_cjk()
+    } else {
+        base.width()
+    };
+    width.unwrap_or(1).clamp(1, 2)
+}
+
+/// The number of cells a grapheme occupies when it starts at `cell`. Tabs
+/// advance to the next tab stop.
+pub fn cell_width(grapheme: &str, cell: usize, widths: CellWidths) -> usize {
+    if grapheme == "\t" {
+        widths.tab_width - cell % widths.tab_width
+    } else {
+        grapheme_width(grapheme, widths.ambiguous_wide)
+    }
+}
+
+pub fn text_width(text: &str, mut cell: usize, widths: CellWidths) -> usize {
+    let start = cell;
+    for grapheme in text.graphemes(true) {
+        cell += cell_width(grapheme, cell, widths);
+    }
+    cell - start
+}
+
+/// Splits `line` into rows no wider than `width`, breaking at word boundaries
+/// where possible. Continuation rows are indented to match the line's leading
+/// whitespace as long as that leaves room for text.
+pub fn wrap_line(text: &str, line: &Row, width: usize, widths: CellWidths, rows: &mut Vec<Row>) {
+    let content = &text[line.bytes.clone()];
+    let leading = content.len() - content.trim_start_matches([' ', '\t']).len();
+    let indent = text_width(&content[..leading], 0, widths);
+    let indent = if indent * 2 > width { 0 } else { indent };
+
+    let mut row = Row {
+        last: false,
+        ..line.clone()
+    };
+    let mut offset = line.offset;
+    let mut cell = 0;
+    let mut break_at = |idx: usize, offset: u32, cell: &mut usize, row: &mut Row| {
+        let end = line.bytes.start + idx;
+        rows.push(Row {
+            bytes: row.bytes.start..end,
+            ..row.clone()
+        });
+        row.bytes.start = end;
+        row.offset = offset;
+        row.indent = indent;
+        *cell = indent;
+    };
+
+    for (idx, word) in content.split_word_bound_indices() {
+        // Whitespace is allowed to hang past the edge rather than starting the next
+        // row with it.
+        if word.chars().all(char::is_whitespace) {
+            cell += text_width(word, cell, widths);
+            offset += word.encode_utf16().count() as u32;
+            continue;
+        }
+
+        let word_width = text_width(word, cell, widths);
+        let row_start = row.bytes.start - line.bytes.start;
+        if cell + word_width > width && idx > row_start {
+            break_at(idx, offset, &mut cell, &mut row);
+        }
+
+        if cell + word_width <= width {
+            cell += word_width;
+            offset += word.encode_utf16().count() as u32;
+            continue;
+        }
+
+        // The word is wider than a row on its own, so it has to be broken up.
+        for (grapheme_idx, grapheme) in word.grapheme_indices(true) {
+            let grapheme_width = cell_width(grapheme, cell, widths);
+            let row_start = row.bytes.start - line.bytes.start;
+            if cell + grapheme_width > width && idx + grapheme_idx > row_start {
+                break_at(idx + grapheme_idx, offset, &mut cell, &mut row);
+            }
+            cell += grapheme_width;
+            offset += grapheme.encode_utf16().count() as u32;
+        }
+    }
+
+    rows.push(Row {
+        bytes: row.bytes.start..line.bytes.end,
+        last: true,
+        ..row
+    });
+}
+
+/// Follows each line with `spacing` blank ones.
+fn spaced(lines: Vec<Line<'_>>, spacing: usize) -> Vec<Line<'_>> {
+    if spacing == 0 {
+        return lines;
+    }
+    lines
+        .into_iter()
+        .flat_map(|line| std::iter::once(line).chain(std::iter::repeat_n(Line::default(), spacing)))
+        .collect()
+}
+
+/// Empties `buffer` and hands back its allocation for spans of another
+/// lifetime. Collecting a vector's own iterator into items of the same layout
+/// reuses the allocation rather than making a new one.
+fn recycle<'a>(mut buffer: Vec<Span<'_>>) -> Vec<Span<'a>> {
+    buffer.clear();
+    buffer.into_iter().map(|_| unreachable!()).collect()
+}
+
+/// Draws `line` into the row at `area`, starting `skip` cells in so the text
+/// can be scrolled sideways. A wide glyph cut by the left edge is left blank.
+fn render_scrolled(line: &Line, skip: usize, area: Rect, buf: &mut Buffer) {
+    let mut cell = 0;
+    for grapheme in line.styled_graphemes(Style::default()) {
+        let start = cell;
+        cell += grapheme_width(grapheme.symbol, false);
+        if start < skip {
+            continue;
+        }
+        if cell - skip > area.width as usize {
+            break;
+        }
+        buf[(area.x + (start - skip) as u16, area.y)]
+            .set_symbol(grapheme.symbol)
+            .set_style(grapheme.style);
+    }
+}
+
+/// Normalizes the textarea's selection bounds into the range of UTF-16 offsets
+/// that should be highlighted. The caret is drawn separately, so an empty
+/// selection highlights nothing.
+pub fn selection_range(start: u32, end: u32) -> Range<u32> {
+    start.min(end)..start.max(end)
+}
+
+pub struct LineBuilder<'a> {
+    pub line: &'a str,
+    pub spans: Vec<Span<'a>>,
+    pub run: Range<usize>,
+    pub style: Style,
+}
+
+impl<'a> LineBuilder<'a> {
+    /// Builds onto `spans`, which is cleared first so a buffer can be reused.
+    pub fn new(line: &'a str, mut spans: Vec<Span<'a>>) -> Self {
+        spans.clear();
+        Self {
+            line,
+            spans,
+            run: 0..0,
+            style: Style::default(),
+        }
+    }
+
+    /// Appends a slice of the line, extending the current span when it is
+    /// contiguous with it and shares its style.
+    pub fn push(&mut self, bytes: Range<usize>, style: Style) {
+        if style != self.style || bytes.start != self.run.end {
+            self.flush();
+            self.run = bytes.start..bytes.start;
+            self.style = style;
+        }
+        self.run.end = bytes.end;
+    }
+
+    /// Appends text that is displayed in place of part of the line.
+    pub fn push_str(&mut self, text: impl Into<Cow<'a, str>>, style: Style) {
+        self.flush();
+        self.spans.push(Span::styled(text, style));
+    }
+
+    pub fn flush(&mut self) {
+        if !self.run.is_empty() {
+            self.spans
+                .push(Span::styled(&self.line[self.run.clone()], self.style));
+        }
+        self.run = self.run.end..self.run.end;
+    }
+
+    pub fn finish(mut self) -> Line<'a> {
+        self.flush();
+        Line::from(self.spans)
+    }
+}
+
+pub struct Caret {
+    pub row: usize,
+    pub col: usize,
+    pub cell: usize,
+    pub width: usize,
+    pub line_width: usize,
+}
+
+impl Caret {
+    pub fn locate(text: &str, rows: &[Row], offset: u32, widths: CellWidths) -> Self {
+        let row = rows
+            .partition_point(|row| row.offset <= offset)
+            .saturating_sub(1);
+        let line = &rows[row];
+
+        let mut caret = Self {
+            row,
+            col: 0,
+            cell: line.indent,
+            width: 1,
+            line_width: line.indent,
+        };
+        let mut cur = line.offset;
+        for grapheme in text[line.bytes.clone()].graphemes(true) {
+            let width = cell_width(grapheme, caret.line_width, widths);
+            if cur < offset {
+                caret.col += 1;
+                caret.cell += width;
+            } else if cur == offset {
+                caret.width = width;
+            }
+            cur += grapheme.encode_utf16().count() as u32;
+            caret.line_width += width;
+        }
+
+        caret
+    }
+}
+
+pub struct Editor<'a> {
+    pub text: &'a str,
+    pub lines: &'a [Row],
+    pub wrap: bool,
+    pub selection: Range<u32>,
+    /// Find matches to highlight, in order.
+    pub matches: &'a [Range<u32>],
+    /// Syntax highlighting for the whole document, in order.
+    pub tokens: &'a [Token],
+    pub caret: u32,
+    /// The bracket beside the caret and its partner.
+    pub brackets: Option<BracketMatch>,
+    /// The text an IME is composing, which is underlined until it is
+    /// committed.
+    pub composition: Option<Range<u32>>,
+    pub gutter_min_width: Option<u16>,
+    pub widths: CellWidths,
+    pub show_whitespace: bool,
+    pub flag_trailing_whitespace: bool,
+    /// The cell to draw the line length guide in, counted from the start of a
+    /// row.
+    pub ruler: Option<usize>,
+    pub typewriter: bool,
+    pub smooth_scroll: bool,
+    /// Blank rows drawn after each row of text.
+    pub spacing: usize,
+    /// Room left between the border and the gutter and text.
+    pub padding: Margin,
+    pub caret_style: CaretStyle,
+    pub theme: Theme,
+}
+
+impl<'a> Editor<'a> {
+    /// Marks the bracket beside the caret and its partner, or just the bracket
+    /// when it has no partner.
+    fn bracket_style(&self, byte: usize) -> Style {
+        match self.brackets {
+            Some(BracketMatch {
+                bracket,
+                partner: None,
+            }) if bracket == byte => Style::new().fg(self.theme.unmatched_bracket).bold(),
+            Some(BracketMatch {
+                bracket,
+                partner: Some(partner),
+            }) if bracket == byte || partner == byte => {
+                Style::new().bg(self.theme.bracket_match).bold()
+            }
+            _ => Style::default(),
+        }
+    }
+
+    /// Highlights `row` into a line made of `spans`, whose allocation it keeps.
+    pub fn highlight_row(&self, row: &Row, spans: Vec<Span<'a>>) -> Line<'a> {
+        let widths = self.widths;
+        let show_whitespace = self.show_whitespace;
+        let line = &self.text[row.bytes.clone()];
+        // Only the last row of a line can end in trailing whitespace. The cell standing
+        // in for the newline is added separately and is never flagged.
+        let trailing = if self.flag_trailing_whitespace && row.last {
+            line.trim_end_matches([' ', '\t']).len()
+        } else {
+            line.len()
+        };
+
+        let highlight = |offset: u32, trailing: bool| {
+            if self.selection.contains(&offset) {
+                let mut style = Style::new().bg(self.theme.selection);
+                if let Some(foreground) = self.theme.selection_foreground {
+                    style = style.fg(foreground);
+                }
+                if self.theme.bold_selection {
+                    style = style.bold();
+                }
+                if self.theme.reverse_video {
+                    style = style.reversed();
+                }
+                style
+            } else if in_ranges(self.matches, offset) {
+                Style::new()
+                    .fg(self.theme.find_match_foreground)
+                    .bg(self.theme.find_match)
+            } else if trailing {
+                Style::new().bg(self.theme.trailing_whitespace)
+            } else {
+                Style::default()
+            }
+        };
+
+        let mut builder = LineBuilder::new(line, spans);
+        if row.indent > 0 {
+            builder.push_str(" ".repeat(row.indent), Style::default());
+        }
+
+        let mut offset = row.offset;
+        let mut cell = row.indent;
+        for (idx, grapheme) in line.grapheme_indices(true) {
+            let width = cell_width(grapheme, cell, widths);
+            // Whitespace and control glyphs only change what is drawn. Offsets and cells
+            // still advance by the real character.
+            // Highlights are patched over the token's color, so selected code
+            // still shows as selected.
+            let byte = row.bytes.start + idx;
+            let style = token_style(self.tokens, byte)
+                .patch(self.bracket_style(byte))
+                .patch(highlight(offset, idx >= trailing));
+            let style = if self
+                .composition
+                .as_ref()
+                .is_some_and(|composition| composition.contains(&offset))
+            {
+                style.underlined()
+            } else {
+                style
+            };
+            if grapheme == "\t" && show_whitespace {
+                builder.push_str("→", style.patch(WHITESPACE_STYLE));
+                builder.push_str(&TAB_SPACES[..width - 1], style);
+            } else if grapheme == "\t" {
+                builder.push_str(&TAB_SPACES[..width], style);
+            } else if grapheme == " " && show_whitespace {
+                builder.push_str("·", style.patch(WHITESPACE_STYLE));
+            } else if let Some(picture) = control_picture(grapheme) {
+                builder.push_str(picture.to_string(), style.patch(CONTROL_STYLE));
+            } else {
+                builder.push(idx..idx + grapheme.len(), style);
+                // The buffer lays glyphs out with the narrow widths, so ambiguous
+                // characters laid out as wide are padded to fill their cells.
+                let drawn = grapheme_width(grapheme, false);
+                if width > drawn {
+                    builder.push_str(&TAB_SPACES[..width - drawn], style);
+                }
+            }
+
+            cell += width;
+            offset += grapheme.encode_utf16().count() as u32;
+        }
+
+        if row.last {
+            // Stands in for the newline so the caret has a cell to sit in at the end of
+            // the line.
+            builder.push_str(" ", highlight(offset, false));
+        }
+
+        builder.finish()
+    }
+}
+
+impl StatefulWidget for Editor<'_> {
+    type State = Pane;
+
+    fn render(self, area: Rect, buf: &mut Buffer, pane: &mut Pane) {
+        // The padding still belongs to the pane, so clicks and scrolling there
+        // reach it.
+        pane.area = area;
+        let area = area.inner(self.padding);
+        let line_count = self.lines.len();
+
+        let digits = self
+            .gutter_min_width
+            .map(|min_width| (line_count.to_string().len() as u16).max(min_width));
+        let (gutter, text_area) = if let Some(digits) = digits {
+            let [gutter, text_area] =
+                Layout::horizontal([Constraint::Length(digits + 1), Constraint::Min(0)])
+                    .areas(area);
+            (Some((gutter, digits)), text_area)
+        } else {
+            (None, area)
+        };
+
+        // A resize changes how many rows fit and, with wrapping, where lines wrap,
+        // and turning wrapping on or off changes the rows altogether. Either way
+        // the scroll is kept on the text that was at the top rather than on the
+        // row number.
+        let relaid = pane.text_area != text_area || self.wrap == pane.wrapped.is_empty();
+        let anchor = relaid.then(|| {
+            let rows = pane.rows(self.lines);
+            rows.get(pane.scroll_row.min(rows.len().saturating_sub(1)))
+                .map_or(0, |row| row.bytes.start)
+        });
+
+        // Taken out of the pane while it is borrowed, and put back once drawn.
+        let mut wrapped = std::mem::take(&mut pane.wrapped);
+        wrapped.clear();
+        if self.wrap {
+            for line in self.lines {
+                wrap_line(
+                    self.text,
+                    line,
+                    text_area.width as usize,
+                    self.widths,
+                    &mut wrapped,
+                );
+            }
+        }
+        let rows: &[Row] = if self.wrap { &wrapped } else { self.lines };
+        let caret = Caret::locate(self.text, rows, self.caret, self.widths);
+
+        if let Some(anchor) = anchor {
+            pane.scroll_row = rows
+                .partition_point(|row| row.bytes.start <= anchor)
+                .saturating_sub(1);
+            pane.shown_row = pane.scroll_row as f64;
+            // The caret is only scrolled to if the new size left it off screen.
+            pane.follow_caret = true;
+        }
+        pane.text_area = text_area;
+        pane.spacing = self.spacing;
+        let follow = std::mem::take(&mut pane.follow_caret);
+        let height = pane.visible_rows();
+        pane.scroll_row = if follow && self.typewriter {
+            // Near either end of the document the caret can't be centered, so it
+            // moves off the middle row instead of the text scrolling past the end.
+            caret
+                .row
+                .saturating_sub(height / 2)
+                .min(rows.len().saturating_sub(height))
+        } else if follow {
+            scroll_into_view(pane.scroll_row, caret.row, height, rows.len())
+        } else {
+            pane.scroll_row.min(rows.len().saturating_sub(height))
+        };
+        let max_row = rows.len().saturating_sub(height);
+        let top = pane.step_scroll(self.smooth_scroll, max_row);
+        let visible = top..(top + height).min(rows.len());
+
+        // The trailing cell for the newline is part of the line's width so the caret
+        // can be scrolled into view at the end of the line.
+        let width = text_area.width as usize;
+        pane.scroll_col = if self.wrap {
+            0
+        } else if follow {
+            scroll_into_view(pane.scroll_col, caret.cell, width, caret.line_width + 1)
+        } else {
+            let widest = rows[visible.clone()]
+                .iter()
+                .map(|row| {
+                    row.indent
+                        + text_width(&self.text[row.bytes.clone()], row.indent, self.widths)
+                        + 1
+                })
+                .max()
+                .unwrap_or_default();
+            pane.scroll_col.min(widest.saturating_sub(width))
+        };
+
+        if let Some((gutter, digits)) = gutter {
+            let numbers = rows[visible.clone()]
+                .iter()
+                .map(|row| {
+                    if row.bytes.start == self.lines[row.line].bytes.start {
+                        Line::from(format!("{:>width$}", row.line + 1, width = digits as usize))
+                    } else {
+                        Line::default()
+                    }
+                })
+                .collect::<Vec<_>>();
+            Paragraph::new(spaced(numbers, self.spacing))
+                .style(Style::new().fg(self.theme.gutter))
+                .render(gutter, buf);
+        }
+
+        let caret_line = rows[caret.row].line;
+        for (y, row) in (text_area.y..)
+            .step_by(self.spacing + 1)
+            .zip(&rows[visible.clone()])
+        {
+            if row.line == caret_line {
+                let current_line = Rect {
+                    y,
+                    height: 1,
+                    ..text_area
+                };
+                buf.set_style(current_line, Style::new().bg(self.theme.current_line));
+            }
+        }
+
+        // The guide only sets a background so the text drawn next stays legible over
+        // it.
+        if let Some(column) = self.ruler
+            && (pane.scroll_col..pane.scroll_col + width).contains(&column)
+        {
+            let ruler = Rect {
+                x: text_area.x + (column - pane.scroll_col) as u16,
+                width: 1,
+                ..text_area
+            };
+            buf.set_style(ruler, Style::new().bg(self.theme.ruler));
+        }
+
+        let mut spans = recycle(std::mem::take(&mut pane.spans));
+        for (y, row) in (text_area.y..)
+            .step_by(self.spacing + 1)
+            .zip(&rows[visible.clone()])
+        {
+            let line = self.highlight_row(row, spans);
+            let area = Rect {
+                y,
+                height: 1,
+                ..text_area
+            };
+            render_scrolled(&line, pane.scroll_col, area, buf);
+            spans = line.spans;
+        }
+        pane.spans = recycle(spans);
+
+        let columns = pane.scroll_col..pane.scroll_col + text_area.width as usize;
+        if visible.contains(&caret.row) && columns.contains(&caret.cell) {
+            let caret_area = Rect {
+                x: text_area.x + (caret.cell - pane.scroll_col) as u16,
+                y: text_area.y + ((caret.row - top) * (self.spacing + 1)) as u16,
+                width: caret.width as u16,
+                height: 1,
+            }
+            .intersection(text_area);
+            self.caret_style.render(caret_area, buf, self.theme);
+        }
+
+        pane.wrapped = wrapped;
+    }
+}
+
+pub const MINIMAP_WIDTH: u16 = 16;
+/// How many columns of text each cell of the minimap stands for.
+pub const MINIMAP_COLUMNS_PER_CELL: usize = 4;
+
+/// How many lines each row of a minimap `height` rows tall stands for, so that
+/// the whole document fits.
+pub fn minimap_lines_per_row(line_count: usize, height: u16) -> usize {
+    line_count.div_ceil(height.max(1) as usize).max(1)
+}
+
+/// A compressed overview of the whole document. Each row stands for a run of
+/// lines, and each cell is filled where any of them has text in the columns it
+/// covers.
+pub struct Minimap<'a> {
+    pub text: &'a str,
+    pub lines: &'a [Row],
+    /// The lines on screen, which are marked as the viewport.
+    pub visible: Range<usize>,
+    pub widths: CellWidths,
+    pub theme: Theme,
+}
+
+impl Widget for Minimap<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        buf.set_style(area, self.theme.base());
+        let per_row = minimap_lines_per_row(self.lines.len(), area.height);
+        let columns = area.width as usize * MINIMAP_COLUMNS_PER_CELL;
+
+        for (y, group) in self
+            .lines
+            .chunks(per_row)
+            .enumerate()
+            .take(area.height as usize)
+        {
+            let y = area.y + y as u16;
+            let mut filled = vec![false; area.width as usize];
+            for line in group {
+                let mut cell = 0;
+                for grapheme in self.text[line.bytes.clone()].graphemes(true) {
+                    if cell >= columns {
+                        break;
+                    }
+                    if !grapheme.chars().all(char::is_whitespace) {
+                        filled[cell / MINIMAP_COLUMNS_PER_CELL] = true;
+                    }
+                    cell += cell_width(grapheme, cell, self.widths);
+                }
+            }
+
+            let first = group[0].line;
+            let in_view = first < self.visible.end && first + group.len() > self.visible.start;
+            let style = if in_view {
+                Style::new()
+                    .fg(self.theme.foreground)
+                    .bg(self.theme.current_line)
+            } else {
+                Style::new().fg(self.theme.gutter)
+            };
+            for (x, filled) in filled.into_iter().enumerate() {
+                if let Some(cell) = buf.cell_mut((area.x + x as u16, y)) {
+                    cell.set_symbol(if filled { "▬" } else { " " })
+                        .set_style(style);
+                }
+            }
+        }
+    }
+}
+
+pub const DIFF_CONTEXT_LINES: usize = 3;
+
+/// A unified diff from the text as last loaded or saved to the document,
+/// with unchanged stretches cut down to a few lines of context around each
+/// change.
+pub fn diff_lines(saved: &str, text: &str, tab_width: usize, theme: Theme) -> Vec<Line<'static>> {
+    let diff = TextDiff::from_lines(saved, text);
+    let mut lines = vec![];
+    for group in diff.grouped_ops(DIFF_CONTEXT_LINES) {
+        let (Some(first), Some(last)) = (group.first(), group.last()) else {
+            continue;
+        };
+        let old = first.old_range().start..last.old_range().end;
+        let new = first.new_range().start..last.new_range().end;
+        lines.push(Line::styled(
+            format!(
+                "@@ -{},{} +{},{} @@",
+                old.start + 1,
+                old.len(),
+                new.start + 1,
+                new.len()
+            ),
+            Style::new().fg(theme.diff_hunk),
+        ));
+        for change in group.iter().flat_map(|op| diff.iter_changes(op)) {
+            let (marker, style) = match change.tag() {
+                ChangeTag::Equal => (' ', Style::default()),
+                ChangeTag::Delete => ('-', Style::new().bg(theme.diff_removed)),
+                ChangeTag::Insert => ('+', Style::new().bg(theme.diff_added)),
+            };
+            let content = change
+                .value()
+                .trim_end_matches('\n')
+                .replace('\t', &TAB_SPACES[..tab_width]);
+            lines.push(Line::from(vec![
+                Span::styled(format!("{marker} "), style.add_modifier(Modifier::BOLD)),
+                Span::styled(content, style),
+            ]));
+        }
+    }
+    lines
+}
+
+/// The unsaved changes, drawn from the lines [`diff_lines`] made of them.
+pub struct DiffView<'a> {
+    pub lines: &'a [Line<'static>],
+    pub theme: Theme,
+}
+
+impl StatefulWidget for DiffView<'_> {
+    /// The row scrolled to the top, which is clamped to the diff's length.
+    type State = usize;
+
+    fn render(self, area: Rect, buf: &mut Buffer, scroll: &mut usize) {
+        Clear.render(area, buf);
+        let block = Block::bordered()
+            .border_set(border::ROUNDED)
+            .title(" Unsaved changes ")
+            .title_bottom(" F9 to close ")
+            .style(self.theme.base());
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        if self.lines.is_empty() {
+            *scroll = 0;
+            Paragraph::new("No unsaved changes")
+                .style(Style::new().fg(self.theme.gutter))
+                .render(inner, buf);
+            return;
+        }
+        *scroll = (*scroll).min(self.lines.len().saturating_sub(inner.height as usize));
+        let visible = self
+            .lines
+            .iter()
+            .skip(*scroll)
+            .take(inner.height as usize)
+            .cloned()
+            .collect::<Vec<_>>();
+        Paragraph::new(visible).render(inner, buf);
+    }
+}
+
+/// A whole frame: the bordered editor, the find bar, the status line and any
+/// overlays. Nothing here touches the DOM, so a frame can be rendered into a
+/// plain [`Buffer`].
+pub struct Screen<'a> {
+    pub config: &'a Config,
+    pub text: &'a str,
+    pub cache: &'a mut RenderCache,
+    pub selection: Range<u32>,
+    pub caret: u32,
+    pub find: &'a FindBar,
+    pub palette: &'a Palette,
+    pub go_to_line: &'a GoToLine,
+    pub confirm: &'a Confirm,
+    pub help: &'a Help,
+    pub stats: &'a FrameStats,
+    /// Shown in the border, marked when `dirty`.
+    pub file_name: &'a str,
+    pub dirty: bool,
+    pub save_state: SaveState,
+    /// The text as last loaded or saved, for showing the unsaved changes.
+    pub saved: &'a str,
+    /// The text an IME is composing, which ends at the caret.
+    pub composition: Option<&'a str>,
+}
+
+impl StatefulWidget for Screen<'_> {
+    type State = ViewState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, view: &mut ViewState) {
+        let Self {
+            config,
+            text,
+            cache,
+            selection,
+            caret,
+            find,
+            palette,
+            go_to_line,
+            confirm,
+            help,
+            stats,
+            file_name,
+            dirty,
+            save_state,
+            saved,
+            composition,
+        } = self;
+
+        cache.index_lines(text);
+        cache.tokenize(text, config.language);
+        let located = Caret::locate(text, &cache.lines, caret, config.widths());
+        let brackets = match_bracket(text, byte_at(text, caret));
+        // Browsers put the composition into the textarea as it is typed, so it
+        // only needs marking. It is left unmarked if the value doesn't hold it.
+        let composition = composition.and_then(|composed| {
+            let start = caret.checked_sub(composed.encode_utf16().count() as u32)?;
+            (text[byte_at(text, start)..byte_at(text, caret)] == *composed).then_some(start..caret)
+        });
+
+        cache.count(text, &selection);
+        let counts = cache.counts;
+        let mut status = format!(
+            " Ln {}, Col {} | {} lines, {} words, {} chars",
+            located.row + 1,
+            located.col + 1,
+            counts.lines,
+            counts.words,
+            counts.chars
+        );
+        if counts.selected {
+            status.push_str(" selected");
+        }
+        status.push_str(" | ");
+        status.push_str(config.line_ending.label());
+        if config.wrap {
+            status.push_str(" | Wrap");
+        }
+        if config.read_only {
+            status.push_str(" | Read-only");
+        }
+        if let Some(label) = save_state.label() {
+            status.push_str(" | ");
+            status.push_str(label);
+        }
+        if let Some(message) = &view.message {
+            status.push_str(" | ");
+            status.push_str(&message.text);
+        }
+
+        let show_status = config.status_bar || view.message.is_some();
+        let [editor_area, find_area, status_area] = Layout::vertical([
+            Constraint::Min(0),
+            Constraint::Length(find.height()),
+            Constraint::Length(u16::from(show_status)),
+        ])
+        .areas(area);
+        if find.open {
+            Paragraph::new(find.prompt(&selection)).render(find_area, buf);
+        }
+        Paragraph::new(status)
+            .style(config.theme.base())
+            .reversed()
+            .render(status_area, buf);
+
+        let [editor_area, minimap_area] = Layout::horizontal([
+            Constraint::Min(0),
+            Constraint::Length(if config.minimap { MINIMAP_WIDTH } else { 0 }),
+        ])
+        .areas(editor_area);
+
+        let pane_areas = match view.split {
+            Some(direction) => Layout::new(
+                direction,
+                [Constraint::Percentage(50), Constraint::Percentage(50)],
+            )
+            .split(editor_area)
+            .to_vec(),
+            None => vec![editor_area],
+        };
+        for (idx, pane_area) in pane_areas.into_iter().enumerate() {
+            // Only a split view needs to show which pane is active.
+            let border_style = if view.split.is_some() && idx != view.active {
+                Style::new().fg(config.theme.gutter)
+            } else {
+                Style::default()
+            };
+            // Without a border the title would cost a row of text, so it is left
+            // out too.
+            let block = match config.border.set() {
+                Some(set) => Block::bordered().border_set(set).title(if dirty {
+                    format!(" {file_name} ● ")
+                } else {
+                    format!(" {file_name} ")
+                }),
+                None => Block::new(),
+            }
+            .border_style(border_style)
+            .style(config.theme.base());
+            let inner = block.inner(pane_area);
+            block.render(pane_area, buf);
+
+            let smooth_scroll = config.smooth_scroll && !view.reduced_motion;
+            Editor {
+                text,
+                lines: &cache.lines,
+                wrap: config.wrap,
+                selection: selection.clone(),
+                matches: &find.matches,
+                tokens: &cache.tokens,
+                caret,
+                brackets,
+                composition,
+                gutter_min_width: config.line_numbers.then_some(config.gutter_min_width),
+                widths: config.widths(),
+                show_whitespace: config.show_whitespace,
+                flag_trailing_whitespace: config.flag_trailing_whitespace,
+                ruler: config.show_ruler.then_some(config.ruler_column),
+                typewriter: config.typewriter,
+                smooth_scroll,
+                spacing: config.line_spacing.blank_rows(),
+                padding: config.padding,
+                caret_style: config.caret_style,
+                theme: config.theme,
+            }
+            .render(inner, buf, &mut view.panes[idx]);
+
+            // Drawn over the right border, or over the padding without one.
+            let pane = &mut view.panes[idx];
+            let rows = pane.rows(&cache.lines).len();
+            let height = pane.visible_rows();
+            let track = if config.border.set().is_some() {
+                pane_area.inner(Margin::new(0, 1))
+            } else {
+                pane_area
+            };
+            pane.scrollbar = (rows > height && !track.is_empty()).then(|| Rect {
+                x: track.right() - 1,
+                width: 1,
+                ..track
+            });
+            if let Some(scrollbar) = pane.scrollbar {
+                let mut state = ScrollbarState::new(rows - height + 1)
+                    .position(pane.top_row())
+                    .viewport_content_length(height);
+                Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                    .begin_symbol(None)
+                    .end_symbol(None)
+                    .track_symbol(Some("│"))
+                    .track_style(Style::new().fg(config.theme.gutter))
+                    .thumb_symbol("┃")
+                    .thumb_style(Style::new().fg(config.theme.foreground))
+                    .render(scrollbar, buf, &mut state);
+            }
+        }
+
+        view.minimap_area = (config.minimap && !minimap_area.is_empty()).then_some(minimap_area);
+        if view.minimap_area.is_some() {
+            let pane = view.pane();
+            let rows = pane.rows(&cache.lines);
+            let top = pane.top_row().min(rows.len().saturating_sub(1));
+            let bottom = (top + pane.visible_rows())
+                .min(rows.len())
+                .saturating_sub(1);
+            Minimap {
+                text,
+                lines: &cache.lines,
+                visible: rows[top].line..rows[bottom].line + 1,
+                widths: config.widths(),
+                theme: config.theme,
+            }
+            .render(minimap_area, buf);
+        }
+        if let Some(scroll) = &mut view.diff_scroll {
+            cache.diff(saved, text, config.tab_width, config.theme);
+            DiffView {
+                lines: &cache.changes,
+                theme: config.theme,
+            }
+            .render(editor_area, buf, scroll);
+        }
+
+        if go_to_line.open {
+            PromptOverlay {
+                title: " Go to line ",
+                label: format!("Line (1-{}): {}▏", cache.lines.len(), go_to_line.input),
+            }
+            .render(area, buf);
+        }
+        if palette.open {
+            palette.render(area, buf);
+        }
+        if let Some(prompt) = confirm.prompt() {
+            prompt.render(area, buf);
+        }
+        if help.open {
+            help.render(area, buf);
+        }
+        if stats.visible {
+            let label = stats.label();
+            let width = (label.len() as u16).min(area.width);
+            let corner = Rect {
+                x: area.right() - width,
+                width,
+                height: 1.min(area.height),
+                ..area
+            };
+            Clear.render(corner, buf);
+            Paragraph::new(label)
+                .style(config.theme.base())
+                .reversed()
+                .render(corner, buf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+
+    const WIDTHS: CellWidths = CellWidths {
+        tab_width: 4,
+        ambiguous_wide: false,
+    };
+
+    fn lines(text: &str) -> Vec<Row> {
+        let mut cache = RenderCache::default();
+        cache.index_lines(text);
+        cache.lines
+    }
+
+    /// An editor with every option that changes what is drawn turned off.
+    fn editor<'a>(text: &'a str, lines: &'a [Row]) -> Editor<'a> {
+        Editor {
+            text,
+            lines,
+            wrap: false,
+            selection: 0..0,
+            matches: &[],
+            tokens: &[],
+            caret: 0,
+            brackets: None,
+            composition: None,
+            gutter_min_width: None,
+            widths: WIDTHS,
+            show_whitespace: false,
+            flag_trailing_whitespace: false,
+            ruler: None,
+            typewriter: false,
+            smooth_scroll: false,
+            spacing: 0,
+            padding: Margin::new(0, 0),
+            // Leaves the background alone, so it doesn't hide the selection.
+            caret_style: CaretStyle::Underline,
+            theme: Theme::DARK,
+        }
+    }
+
+    fn render(editor: Editor, width: u16, height: u16, pane: &mut Pane) -> Buffer {
+        let area = Rect::new(0, 0, width, height);
+        let mut buf = Buffer::empty(area);
+        editor.render(area, &mut buf, pane);
+        buf
+    }
+
+    const SELECTED: Style = Style::new().bg(Theme::DARK.selection);
+
+    fn spans<'a>(line: &'a Line) -> Vec<(&'a str, Style)> {
+        line.spans
+            .iter()
+            .map(|span| (span.content.as_ref(), span.style))
+            .collect()
+    }
+
+    /// The columns of row `y` drawn with the selection's background.
+    fn selected_cells(buf: &Buffer, y: u16) -> Vec<u16> {
+        (0..buf.area.width)
+            .filter(|&x| buf[(x, y)].bg == Theme::DARK.selection)
+            .collect()
+    }
+
+    #[wasm_bindgen_test]
+    fn selection_is_measured_in_utf16() {
+        // Each emoji is two UTF-16 code units and two cells, so "😊" is 2..4.
+        let text = "ab😊🦀cd";
+        let lines = lines(text);
+
+        let selection = selection_range(4, 2);
+        assert_eq!(selection, 2..4);
+        let buf = render(
+            Editor {
+                selection,
+                caret: 2,
+                ..editor(text, &lines)
+            },
+            16,
+            1,
+            &mut Pane::default(),
+        );
+        assert_eq!(selected_cells(&buf, 0), [2]);
+        assert_eq!(buf[(2, 0)].symbol(), "😊");
+
+        let buf = render(
+            Editor {
+                selection: selection_range(4, 6),
+                caret: 6,
+                ..editor(text, &lines)
+            },
+            16,
+            1,
+            &mut Pane::default(),
+        );
+        assert_eq!(selected_cells(&buf, 0), [4]);
+        assert_eq!(buf[(4, 0)].symbol(), "🦀");
+    }
+
+    #[wasm_bindgen_test]
+    fn long_lines_scroll_to_keep_the_caret_visible() {
+        // The tab takes four cells, so the caret's cell runs three ahead of its
+        // offset after it.
+        let text = format!("\t{}", "x".repeat(499));
+        let lines = lines(&text);
+        let mut pane = Pane::default();
+        let (width, len) = (20, text.len() as u32);
+
+        for caret in (0..=len).chain((0..len).rev()) {
+            let located = Caret::locate(&text, &lines, caret, WIDTHS);
+            assert_eq!(
+                located.cell,
+                if caret == 0 { 0 } else { caret as usize + 3 }
+            );
+
+            pane.follow_caret = true;
+            let buf = render(
+                Editor {
+                    caret,
+                    caret_style: CaretStyle::Block,
+                    ..editor(&text, &lines)
+                },
+                width,
+                1,
+                &mut pane,
+            );
+            let columns = pane.scroll_col..pane.scroll_col + width as usize;
+            assert!(
+                columns.contains(&located.cell),
+                "caret {caret} is off screen"
+            );
+            let x = (located.cell - pane.scroll_col) as u16;
+            assert_eq!(buf[(x, 0)].bg, Theme::DARK.caret);
+        }
+        assert_eq!(pane.scroll_col, 0);
+    }
+
+    #[wasm_bindgen_test]
+    fn scroll_into_view_moves_only_as_far_as_needed() {
+        assert_eq!(scroll_into_view(0, 5, 20, 500), 0);
+        assert_eq!(scroll_into_view(0, 20, 20, 500), 1);
+        assert_eq!(scroll_into_view(100, 90, 20, 500), 90);
+        assert_eq!(scroll_into_view(100, 110, 20, 500), 100);
+        assert_eq!(scroll_into_view(490, 499, 20, 500), 480);
+    }
+
+    /// Times whole frames of a 10,000-line document: indexing its lines, then
+    /// laying out and drawing the rows on screen. How fast a frame is depends
+    /// on the machine, so it only fails once a frame no longer fits in the
+    /// budget for drawing at 60 fps.
+    #[wasm_bindgen_test]
+    fn bench_redraw_of_a_10k_line_document() {
+        const FRAMES: u32 = 100;
+        const FRAME_BUDGET_MS: f64 = 1000.0 / 60.0;
+
+        let text = (0..10_000)
+            .map(|line| format!("line {line} of the document"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let area = Rect::new(0, 0, 80, 24);
+        let mut buf = Buffer::empty(area);
+        let mut cache = RenderCache::default();
+        let mut pane = Pane::default();
+
+        let started = crate::app::now_ms();
+        for frame in 0..FRAMES {
+            cache.index_lines(&text);
+            Editor {
+                // Moving the caret keeps each frame from matching the last.
+                caret: frame * 100,
+                ..editor(&text, &cache.lines)
+            }
+            .render(area, &mut buf, &mut pane);
+        }
+        let elapsed = crate::app::now_ms() - started;
+        wasm_bindgen_test::console_log!(
+            "redraw of 10k lines: {:.3} ms/frame",
+            elapsed / FRAMES as f64
+        );
+
+        assert_eq!(cache.lines.len(), 10_000);
+        assert_eq!(buf[(0, 0)].symbol(), "l");
+        assert!(
+            elapsed / (FRAMES as f64) < FRAME_BUDGET_MS,
+            "{elapsed} ms for {FRAMES} frames"
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn unsaved_changes_are_diffed_once_per_change() {
+        let text = |line: &Line| {
+            line.spans
+                .iter()
+                .map(|span| span.content.as_ref())
+                .collect::<String>()
+        };
+        let mut cache = RenderCache::default();
+        cache.diff("a\n\tb\n", "a\nc\n", 2, Theme::DARK);
+        assert_eq!(
+            cache.changes.iter().map(text).collect::<Vec<_>>(),
+            ["@@ -1,2 +1,2 @@", "  a", "-   b", "+ c"]
+        );
+
+        let diffed = cache.changes_for;
+        cache.diff("a\n\tb\n", "a\nc\n", 2, Theme::DARK);
+        assert_eq!(cache.changes_for, diffed);
+        cache.diff("a\n\tb\n", "a\n\tb\n", 2, Theme::DARK);
+        assert!(cache.changes.is_empty());
+    }
+
+    #[wasm_bindgen_test]
+    fn caret_at_the_end_of_a_line_sits_on_the_newline_cell() {
+        let text = "ab\ncd";
+        let lines = lines(text);
+        let editor = Editor {
+            caret: 2,
+            caret_style: CaretStyle::Block,
+            ..editor(text, &lines)
+        };
+        assert_eq!(
+            spans(&editor.highlight_row(&lines[0], vec![])),
+            [("ab", Style::default()), (" ", Style::default())]
+        );
+
+        let buf = render(editor, 8, 2, &mut Pane::default());
+        assert_eq!(buf[(2, 0)].symbol(), " ");
+        assert_eq!(buf[(2, 0)].bg, Theme::DARK.caret);
+        assert_eq!(buf[(0, 0)].bg, Theme::DARK.current_line);
+        assert_eq!(buf[(0, 1)].bg, Color::Reset);
+    }
+
+    #[wasm_bindgen_test]
+    fn selection_across_a_newline_highlights_the_newline_cell() {
+        let text = "ab\ncd";
+        let lines = lines(text);
+        let editor = Editor {
+            selection: 1..4,
+            caret: 4,
+            ..editor(text, &lines)
+        };
+        assert_eq!(
+            spans(&editor.highlight_row(&lines[0], vec![])),
+            [("a", Style::default()), ("b", SELECTED), (" ", SELECTED)]
+        );
+        assert_eq!(
+            spans(&editor.highlight_row(&lines[1], vec![])),
+            [
+                ("c", SELECTED),
+                ("d", Style::default()),
+                (" ", Style::default())
+            ]
+        );
+
+        let buf = render(editor, 8, 2, &mut Pane::default());
+        assert_eq!(selected_cells(&buf, 0), [1, 2]);
+        assert_eq!(selected_cells(&buf, 1), [0]);
+    }
+
+    #[wasm_bindgen_test]
+    fn highlights_take_their_colors_from_the_theme() {
+        let text = "ab ";
+        let lines = lines(text);
+        let editor = Editor {
+            matches: &[0..1],
+            flag_trailing_whitespace: true,
+            theme: Theme::HIGH_CONTRAST,
+            ..editor(text, &lines)
+        };
+        let theme = Theme::HIGH_CONTRAST;
+        assert_eq!(
+            spans(&editor.highlight_row(&lines[0], vec![])),
+            [
+                (
+                    "a",
+                    Style::new()
+                        .fg(theme.find_match_foreground)
+                        .bg(theme.find_match)
+                ),
+                ("b", Style::default()),
+                (" ", Style::new().bg(theme.trailing_whitespace)),
+                (" ", Style::default())
+            ]
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn the_plain_theme_draws_the_selection_and_caret_in_reverse_video() {
+        let text = "ab";
+        let lines = lines(text);
+        let editor = Editor {
+            selection: 0..1,
+            caret: 1,
+            caret_style: CaretStyle::Block,
+            theme: Theme::PLAIN,
+            ..editor(text, &lines)
+        };
+        let buf = render(editor, 8, 1, &mut Pane::default());
+        assert!(buf[(0, 0)].modifier.contains(Modifier::REVERSED));
+        assert!(buf[(1, 0)].modifier.contains(Modifier::REVERSED));
+        assert!(!buf[(2, 0)].modifier.contains(Modifier::REVERSED));
+        assert_eq!(buf[(1, 0)].bg, Color::Reset);
+    }
+
+    #[wasm_bindgen_test]
+    fn selection_over_a_wide_character_covers_its_cells() {
+        let text = "a中b";
+        let lines = lines(text);
+        let editor = Editor {
+            selection: 1..2,
+            caret: 2,
+            ..editor(text, &lines)
+        };
+        assert_eq!(
+            spans(&editor.highlight_row(&lines[0], vec![])),
+            [
+                ("a", Style::default()),
+                ("中", SELECTED),
+                ("b", Style::default()),
+                (" ", Style::default())
+            ]
+        );
+
+        let caret = Caret::locate(text, &lines, 1, WIDTHS);
+        assert_eq!((caret.cell, caret.width), (1, 2));
+        let caret = Caret::locate(text, &lines, 2, WIDTHS);
+        assert_eq!((caret.col, caret.cell, caret.line_width), (2, 3, 4));
+
+        let buf = render(editor, 8, 1, &mut Pane::default());
+        assert_eq!(selected_cells(&buf, 0), [1]);
+        assert_eq!(buf[(1, 0)].symbol(), "中");
+        assert_eq!(buf[(3, 0)].symbol(), "b");
+        assert!(buf[(3, 0)].modifier.contains(Modifier::UNDERLINED));
+    }
+
+    #[wasm_bindgen_test]
+    fn empty_document_draws_the_caret_in_the_newline_cell() {
+        let lines = lines("");
+        assert_eq!(lines.len(), 1);
+        let editor = Editor {
+            caret_style: CaretStyle::Block,
+            ..editor("", &lines)
+        };
+        assert_eq!(
+            spans(&editor.highlight_row(&lines[0], vec![])),
+            [(" ", Style::default())]
+        );
+
+        let caret = Caret::locate("", &lines, 0, WIDTHS);
+        assert_eq!((caret.row, caret.cell, caret.width), (0, 0, 1));
+        let buf = render(editor, 8, 2, &mut Pane::default());
+        assert_eq!(buf[(0, 0)].bg, Theme::DARK.caret);
+        assert_eq!(buf[(1, 0)].bg, Theme::DARK.current_line);
+    }
+
+    #[wasm_bindgen_test]
+    fn zwj_family_is_one_two_cell_grapheme() {
+        // Man, woman and girl joined by ZWJs: 8 UTF-16 code units.
+        let family = "👨\u{200D}👩\u{200D}👧";
+        assert_eq!(family.graphemes(true).count(), 1);
+        assert_eq!(grapheme_width(family, false), 2);
+
+        let text = format!("a{family}b");
+        let lines = lines(&text);
+        let caret = Caret::locate(&text, &lines, 1, WIDTHS);
+        assert_eq!((caret.col, caret.cell, caret.width), (1, 1, 2));
+        let caret = Caret::locate(&text, &lines, 9, WIDTHS);
+        assert_eq!((caret.col, caret.cell, caret.line_width), (2, 3, 4));
+
+        let buf = render(
+            Editor {
+                selection: 1..9,
+                caret: 9,
+                ..editor(&text, &lines)
+            },
+            8,
+            1,
+            &mut Pane::default(),
+        );
+        assert_eq!(selected_cells(&buf, 0), [1]);
+        assert_eq!(buf[(1, 0)].symbol(), family);
+        assert_eq!(buf[(3, 0)].symbol(), "b");
+        assert!(buf[(3, 0)].modifier.contains(Modifier::UNDERLINED));
+    }
+
+    #[wasm_bindgen_test]
+    fn emoji_sequences_take_their_presentation_width() {
+        for (grapheme, width) in [
+            // Emoji presentation, from VS16.
+            ("\u{2764}\u{FE0F}", 2),
+            // Text presentation, from VS15.
+            ("\u{2764}\u{FE0E}", 1),
+            // A keycap.
+            ("#\u{FE0F}\u{20E3}", 2),
+            // A flag, from a pair of regional indicators.
+            ("\u{1F1FA}\u{1F1F8}", 2),
+        ] {
+            assert_eq!(grapheme.graphemes(true).count(), 1, "{grapheme}");
+            assert_eq!(grapheme_width(grapheme, false), width, "{grapheme}");
+
+            let lines = lines(grapheme);
+            let end = grapheme.encode_utf16().count() as u32;
+            let caret = Caret::locate(grapheme, &lines, end, WIDTHS);
+            assert_eq!((caret.col, caret.cell), (1, width), "{grapheme}");
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn ambiguous_characters_follow_the_width_setting() {
+        let wide = CellWidths {
+            ambiguous_wide: true,
+            ..WIDTHS
+        };
+        assert_eq!(grapheme_width("±", false), 1);
+        assert_eq!(grapheme_width("±", true), 2);
+        // Unambiguous characters keep their width either way.
+        assert_eq!(grapheme_width("a", true), 1);
+        assert_eq!(grapheme_width("中", false), 2);
+
+        let text = "±±a";
+        let lines = lines(text);
+        for (widths, cells) in [(WIDTHS, [0, 1, 2, 3]), (wide, [0, 2, 4, 5])] {
+            for (offset, cell) in cells.into_iter().enumerate() {
+                let caret = Caret::locate(text, &lines, offset as u32, widths);
+                assert_eq!(caret.cell, cell, "offset {offset}, {widths:?}");
+            }
+        }
+        assert_eq!(Caret::locate(text, &lines, 0, wide).width, 2);
+    }
+}
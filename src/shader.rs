@@ -0,0 +1,320 @@
+/// The bindings and vertex stage every post-processing shader is built on.
+pub const FULLSCREEN_SHADER: &str = include_str!("shaders/fullscreen.wgsl");
+pub const CRT_SHADER: &str = include_str!("shaders/crt.wgsl");
+pub const SCANLINES_SHADER: &str = include_str!("shaders/scanlines.wgsl");
+pub const BLOOM_SHADER: &str = include_str!("shaders/bloom.wgsl");
+/// The text as drawn, with only the brightness and contrast applied.
+pub const PLAIN_SHADER: &str = include_str!("shaders/plain.wgsl");
+
+/// Tunables for the CRT effect. The scanline intensity is shared with the
+/// scanlines-only effect.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CrtSettings {
+    /// How far the picture bows outward. 0 is flat.
+    pub curvature: f32,
+    /// How much every other row of pixels is darkened, from 0 to 1.
+    pub scanlines: f32,
+    /// How much the corners fade out, from 0 to 1.
+    pub vignette: f32,
+    /// How many pixels red and blue split apart at the edges.
+    pub aberration: f32,
+}
+
+impl Default for CrtSettings {
+    fn default() -> Self {
+        Self {
+            curvature: 0.04,
+            scanlines: 0.3,
+            vignette: 0.4,
+            aberration: 1.5,
+        }
+    }
+}
+
+impl CrtSettings {
+    /// Scales the whole effect, for turning it up or down live.
+    pub fn scaled(self, factor: f32) -> Self {
+        Self {
+            curvature: self.curvature * factor,
+            scanlines: (self.scanlines * factor).min(1.0),
+            vignette: (self.vignette * factor).min(1.0),
+            aberration: self.aberration * factor,
+        }
+    }
+
+    /// The uniform buffer contents, laid out as `Settings` in
+    /// `fullscreen.wgsl`.
+    fn to_bytes(self, adjustment: Adjustment, resolution: [f32; 2]) -> Vec<u8> {
+        [
+            resolution[0],
+            resolution[1],
+            self.curvature,
+            self.scanlines,
+            self.vignette,
+            self.aberration,
+            adjustment.brightness,
+            adjustment.contrast,
+        ]
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+    }
+}
+
+const SETTINGS_SIZE: u64 = 8 * 4;
+
+/// How much each step of the brightness or contrast changes it by.
+pub const ADJUSTMENT_STEP: f32 = 0.1;
+
+/// Brightness and contrast, applied to the finished picture whatever the
+/// effect.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Adjustment {
+    /// Multiplies every color. 1 leaves them alone.
+    pub brightness: f32,
+    /// Spreads colors away from mid gray, or gathers them toward it below 1.
+    pub contrast: f32,
+}
+
+impl Default for Adjustment {
+    fn default() -> Self {
+        Self {
+            brightness: 1.0,
+            contrast: 1.0,
+        }
+    }
+}
+
+impl Adjustment {
+    /// Keeps both within a range that leaves the text readable. They are
+    /// rounded to hundredths so stepping back and forth returns to exactly
+    /// where it started.
+    pub fn clamped(self) -> Self {
+        let round = |value: f32| (value * 100.0).round() / 100.0;
+        Self {
+            brightness: round(self.brightness.clamp(0.25, 2.0)),
+            contrast: round(self.contrast.clamp(0.25, 3.0)),
+        }
+    }
+
+    pub fn is_neutral(self) -> bool {
+        self == Self::default()
+    }
+}
+
+/// A post-processing pass that draws the terminal's text texture to the
+/// surface through a fragment shader. The shader's `fs_main` is appended to
+/// [`FULLSCREEN_SHADER`], so it can read `text`, `text_sampler` and
+/// `settings`.
+pub struct ShaderPass {
+    pipeline: wgpu::RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    sampler: wgpu::Sampler,
+    settings: wgpu::Buffer,
+}
+
+impl ShaderPass {
+    pub fn new(
+        device: &wgpu::Device,
+        text_view: &wgpu::TextureView,
+        surface_config: &wgpu::SurfaceConfiguration,
+        label: &str,
+        fragment: &str,
+    ) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(format!("{FULLSCREEN_SHADER}\n{fragment}").into()),
+        });
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(label),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_config.format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(label),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let settings = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: SETTINGS_SIZE,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = Self::bind(device, &layout, text_view, &sampler, &settings);
+
+        Self {
+            pipeline,
+            layout,
+            bind_group,
+            sampler,
+            settings,
+        }
+    }
+
+    fn bind(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        text_view: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+        settings: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(text_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: settings.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// Rebinds the text texture, which is recreated when the surface resizes.
+    pub fn resize(&mut self, device: &wgpu::Device, text_view: &wgpu::TextureView) {
+        self.bind_group = Self::bind(
+            device,
+            &self.layout,
+            text_view,
+            &self.sampler,
+            &self.settings,
+        );
+    }
+
+    pub fn process(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        surface_config: &wgpu::SurfaceConfiguration,
+        surface_view: &wgpu::TextureView,
+        settings: CrtSettings,
+        adjustment: Adjustment,
+    ) {
+        let resolution = [surface_config.width as f32, surface_config.height as f32];
+        queue.write_buffer(
+            &self.settings,
+            0,
+            &settings.to_bytes(adjustment, resolution),
+        );
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: surface_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+
+    /// How many floats `Settings` in `fullscreen.wgsl` declares.
+    fn declared_floats() -> usize {
+        let start = FULLSCREEN_SHADER.find("struct Settings {").unwrap();
+        let body = &FULLSCREEN_SHADER[start..];
+        body[..body.find('}').unwrap()]
+            .lines()
+            .skip(1)
+            .filter_map(|field| field.trim().strip_suffix(','))
+            .map(|field| match field.rsplit(':').next().unwrap().trim() {
+                "f32" => 1,
+                "vec2<f32>" => 2,
+                other => panic!("unexpected field type {other}"),
+            })
+            .sum()
+    }
+
+    #[wasm_bindgen_test]
+    fn uniform_bytes_match_the_wgsl_settings() {
+        let bytes = CrtSettings::default().to_bytes(Adjustment::default(), [640.0, 480.0]);
+        assert_eq!(bytes.len() as u64, SETTINGS_SIZE);
+        assert_eq!(bytes.len(), declared_floats() * 4);
+        // Uniform buffers are bound in multiples of 16 bytes.
+        assert_eq!(SETTINGS_SIZE % 16, 0);
+    }
+}
@@ -0,0 +1,31 @@
+// How far apart, in pixels, the glow's samples are taken.
+const SPREAD: f32 = 2.5;
+// Below this brightness a pixel doesn't add to the glow.
+const THRESHOLD: f32 = 0.45;
+const STRENGTH: f32 = 0.9;
+
+fn bright(uv: vec2<f32>) -> vec3<f32> {
+    let color = textureSample(text, text_sampler, uv).rgb;
+    let luma = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    return color * smoothstep(THRESHOLD, 1.0, luma);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(text, text_sampler, in.uv).rgb;
+    let texel = SPREAD / settings.resolution;
+
+    // A 5x5 grid with weights falling off from the middle.
+    var glow = vec3<f32>(0.0);
+    var total = 0.0;
+    for (var y = -2; y <= 2; y++) {
+        for (var x = -2; x <= 2; x++) {
+            let offset = vec2<f32>(f32(x), f32(y));
+            let weight = exp(-dot(offset, offset) / 4.0);
+            glow += bright(in.uv + offset * texel) * weight;
+            total += weight;
+        }
+    }
+
+    return vec4<f32>(adjust(color + glow / total * STRENGTH), 1.0);
+}
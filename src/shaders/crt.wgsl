@@ -0,0 +1,26 @@
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Push samples outward with distance from the center, like a curved tube.
+    let centered = in.uv * 2.0 - 1.0;
+    let bent = centered * (1.0 + settings.curvature * dot(centered, centered));
+    let uv = bent * 0.5 + 0.5;
+    let inside = f32(all(uv >= vec2<f32>(0.0)) && all(uv <= vec2<f32>(1.0)));
+
+    // Red and blue drift apart toward the edges, by up to `aberration` pixels.
+    let shift = centered * settings.aberration / settings.resolution;
+    let color = vec3<f32>(
+        textureSample(text, text_sampler, uv + shift).r,
+        textureSample(text, text_sampler, uv).g,
+        textureSample(text, text_sampler, uv - shift).b,
+    );
+
+    // Darken every other row of pixels.
+    let line = 0.5 + 0.5 * cos(uv.y * settings.resolution.y * 3.14159265);
+    let scanlines = 1.0 - settings.scanlines * (1.0 - line);
+
+    let edge = uv * (1.0 - uv);
+    let falloff = clamp(pow(16.0 * edge.x * edge.y, 0.25), 0.0, 1.0);
+    let vignette = mix(1.0, falloff, settings.vignette);
+
+    return vec4<f32>(adjust(color * scanlines * vignette) * inside, 1.0);
+}
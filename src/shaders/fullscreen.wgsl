@@ -0,0 +1,37 @@
+// Shared by every post-processing shader: the bindings they read from and a
+// vertex stage that covers the screen with one triangle.
+
+struct Settings {
+    resolution: vec2<f32>,
+    curvature: f32,
+    scanlines: f32,
+    vignette: f32,
+    aberration: f32,
+    brightness: f32,
+    contrast: f32,
+}
+
+@group(0) @binding(0) var text: texture_2d<f32>;
+@group(0) @binding(1) var text_sampler: sampler;
+@group(0) @binding(2) var<uniform> settings: Settings;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// The brightness and contrast the user picked, which every effect applies to
+// its final color. Custom shaders can call it too.
+fn adjust(color: vec3<f32>) -> vec3<f32> {
+    let contrasted = clamp((color - 0.5) * settings.contrast + 0.5, vec3<f32>(0.0), vec3<f32>(1.0));
+    return contrasted * settings.brightness;
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
@@ -0,0 +1,4 @@
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(adjust(textureSample(text, text_sampler, in.uv).rgb), 1.0);
+}
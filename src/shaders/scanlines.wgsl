@@ -0,0 +1,6 @@
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(text, text_sampler, in.uv).rgb;
+    let line = 0.5 + 0.5 * cos(in.uv.y * settings.resolution.y * 3.14159265);
+    return vec4<f32>(adjust(color * (1.0 - settings.scanlines * (1.0 - line))), 1.0);
+}
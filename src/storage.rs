@@ -0,0 +1,324 @@
+use wasm_bindgen::{
+    JsCast,
+    JsValue,
+    closure::Closure,
+};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    Blob,
+    BlobPropertyBag,
+    Clipboard,
+    File,
+    HtmlAnchorElement,
+    HtmlInputElement,
+    Storage,
+    Url,
+};
+use winit::event_loop::EventLoopProxy;
+
+use crate::{
+    app::AppEvent,
+    input::read_file,
+};
+
+pub const SAMPLE_TEXT: &str = "This is a simple text editor using ratatui-wgpu.
+
+It even supports emojis! 😊🦀🐁
+On Windows, you can use WIN+. to insert and test this out!";
+
+/// How long the demo waits between graphemes of the sample, unless the page
+/// asks for another speed.
+pub const DEMO_TYPING_MS: f64 = 60.0;
+/// How long the demo leaves the finished sample up before typing it again.
+pub const DEMO_PAUSE_MS: f64 = 3000.0;
+
+pub const STORAGE_KEY: &str = "ratatui-wgpu-ed.document";
+/// Where the last choice of wrapping is kept.
+pub const WRAP_STORAGE_KEY: &str = "ratatui-wgpu-ed.wrap";
+/// Where the last choice of the high-contrast mode is kept.
+pub const HIGH_CONTRAST_STORAGE_KEY: &str = "ratatui-wgpu-ed.high-contrast";
+pub const BRIGHTNESS_STORAGE_KEY: &str = "ratatui-wgpu-ed.brightness";
+pub const CONTRAST_STORAGE_KEY: &str = "ratatui-wgpu-ed.contrast";
+/// How long typing has to pause before the document is auto-saved, unless
+/// the page asks for another interval.
+pub const SAVE_DEBOUNCE_MS: f64 = 500.0;
+
+pub fn local_storage() -> Option<Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+/// A setting remembered across reloads, stored as "true" or "false".
+pub fn stored_flag(key: &str) -> Option<bool> {
+    let value = local_storage()?.get_item(key).ok()??;
+    Some(value == "true")
+}
+
+pub fn store_flag(storage: &Option<Storage>, key: &str, value: bool) {
+    if let Some(store) = storage {
+        let _ = store.set_item(key, if value { "true" } else { "false" });
+    }
+}
+
+pub fn stored_number(key: &str) -> Option<f32> {
+    local_storage()?
+        .get_item(key)
+        .ok()??
+        .parse()
+        .ok()
+        .filter(|value: &f32| value.is_finite())
+}
+
+pub fn store_number(storage: &Option<Storage>, key: &str, value: f32) {
+    if let Some(store) = storage {
+        let _ = store.set_item(key, &value.to_string());
+    }
+}
+
+pub fn save_to_storage(storage: &mut Option<Storage>, text: &str) {
+    let Some(store) = storage else {
+        return;
+    };
+
+    if let Err(err) = store.set_item(STORAGE_KEY, text) {
+        log::warn!("Failed to save the document, continuing without persistence: {err:?}");
+        *storage = None;
+    }
+}
+
+/// The system clipboard, which browsers only give to secure pages. Elsewhere
+/// `navigator.clipboard` is undefined rather than missing a method.
+pub fn clipboard() -> Option<Clipboard> {
+    let navigator = web_sys::window()?.navigator();
+    js_sys::Reflect::has(&navigator, &JsValue::from_str("clipboard"))
+        .ok()?
+        .then(|| navigator.clipboard())
+        .filter(|clipboard| !clipboard.is_undefined())
+}
+
+/// Writes `text` to the system clipboard, returning whether there is one to
+/// write to. The write finishes asynchronously and is reported through `proxy`
+/// once it succeeds.
+pub fn copy_to_clipboard(text: String, proxy: EventLoopProxy<AppEvent>) -> bool {
+    let Some(clipboard) = clipboard() else {
+        return false;
+    };
+
+    wasm_bindgen_futures::spawn_local(async move {
+        match JsFuture::from(clipboard.write_text(&text)).await {
+            Ok(_) => {
+                let _ = proxy.send_event(AppEvent::Message(format!(
+                    "Copied {} characters",
+                    text.chars().count()
+                )));
+            }
+            // Browsers reject the write without permission or a focused page.
+            // Ctrl+C is still left to the browser, which copies the selection
+            // itself.
+            Err(err) => log::warn!("Failed to write to the clipboard: {err:?}"),
+        }
+    });
+    true
+}
+
+pub const DEFAULT_FILE_NAME: &str = "untitled.txt";
+
+pub fn download_text(file_name: &str, text: &str) -> Result<(), JsValue> {
+    let options = BlobPropertyBag::new();
+    options.set_type("text/plain");
+    let blob = Blob::new_with_str_sequence_and_options(
+        &js_sys::Array::of1(&JsValue::from_str(text)),
+        &options,
+    )?;
+    let url = Url::create_object_url_with_blob(&blob)?;
+
+    let window = web_sys::window().ok_or_else(|| JsValue::from_str("no window"))?;
+    let anchor = window
+        .document()
+        .ok_or_else(|| JsValue::from_str("no document"))?
+        .create_element("a")?
+        .dyn_into::<HtmlAnchorElement>()?;
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+
+    // Some browsers start the download after the click returns, so the URL
+    // has to outlive this task.
+    let revoke = Closure::once_into_js(move || {
+        let _ = Url::revoke_object_url(&url);
+    });
+    window.set_timeout_with_callback_and_timeout_and_arguments_0(revoke.unchecked_ref(), 0)?;
+    Ok(())
+}
+
+/// A file picked through the File System Access API, which the document can
+/// be written back to. Only Chromium-based browsers have it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileHandle(JsValue);
+
+// Handles compare by identity, which is an equivalence relation.
+impl Eq for FileHandle {}
+
+impl FileHandle {
+    pub fn name(&self) -> String {
+        js_sys::Reflect::get(&self.0, &"name".into())
+            .ok()
+            .and_then(|name| name.as_string())
+            .unwrap_or_else(|| DEFAULT_FILE_NAME.to_string())
+    }
+}
+
+pub fn file_pickers_available() -> bool {
+    web_sys::window()
+        .and_then(|win| js_sys::Reflect::get(&win, &"showOpenFilePicker".into()).ok())
+        .is_some_and(|picker| picker.is_function())
+}
+
+/// Calls a promise-returning method that web-sys doesn't expose and waits
+/// for the promise.
+async fn call_async(
+    target: &JsValue,
+    method: &str,
+    args: &js_sys::Array,
+) -> Result<JsValue, JsValue> {
+    let function = js_sys::Reflect::get(target, &method.into())?.dyn_into::<js_sys::Function>()?;
+    let promise = js_sys::Reflect::apply(&function, target, args)?.dyn_into::<js_sys::Promise>()?;
+    JsFuture::from(promise).await
+}
+
+/// Pickers reject with an `AbortError` when the user closes them, which is
+/// not worth reporting.
+fn cancelled(err: &JsValue) -> bool {
+    js_sys::Reflect::get(err, &"name".into())
+        .ok()
+        .and_then(|name| name.as_string())
+        .is_some_and(|name| name == "AbortError")
+}
+
+/// Asks for a file to open and reads it, reporting the result through
+/// `proxy`. Browsers without the File System Access API get a plain file
+/// input instead, and the file can't be written back to.
+pub fn open_file(proxy: EventLoopProxy<AppEvent>) {
+    let Some(win) = web_sys::window() else {
+        return;
+    };
+    if !file_pickers_available() {
+        open_with_file_input(&win, proxy);
+        return;
+    }
+
+    wasm_bindgen_futures::spawn_local(async move {
+        let picked = async {
+            let handles = call_async(&win, "showOpenFilePicker", &js_sys::Array::new()).await?;
+            let handle = js_sys::Array::from(&handles).get(0);
+            let file = call_async(&handle, "getFile", &js_sys::Array::new())
+                .await?
+                .dyn_into::<File>()?;
+            Ok::<_, JsValue>((FileHandle(handle), file))
+        };
+        let event = match picked.await {
+            Ok((handle, file)) => read_file(file, Some(handle)).await,
+            Err(err) if cancelled(&err) => return,
+            Err(err) => AppEvent::Message(format!("Failed to open the file: {err:?}")),
+        };
+        let _ = proxy.send_event(event);
+    });
+}
+
+fn open_with_file_input(win: &web_sys::Window, proxy: EventLoopProxy<AppEvent>) {
+    let Some(input) = win
+        .document()
+        .and_then(|doc| doc.create_element("input").ok())
+        .and_then(|input| input.dyn_into::<HtmlInputElement>().ok())
+    else {
+        return;
+    };
+    input.set_type("file");
+
+    let target = input.clone();
+    let on_change = Closure::<dyn FnMut()>::new(move || {
+        let Some(file) = target.files().and_then(|files| files.get(0)) else {
+            return;
+        };
+        let proxy = proxy.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let _ = proxy.send_event(read_file(file, None).await);
+        });
+    });
+    if input
+        .add_event_listener_with_callback("change", on_change.as_ref().unchecked_ref())
+        .is_ok()
+    {
+        on_change.forget();
+        input.click();
+    }
+}
+
+/// Writes `text` to `handle`, or to a file the user picks if there isn't
+/// one yet. Once written, a [`AppEvent::FileSaved`] carrying `saved` and
+/// `auto` is sent so the app can mark the document clean.
+pub fn write_file(
+    handle: Option<FileHandle>,
+    suggested_name: String,
+    text: String,
+    saved: String,
+    auto: bool,
+    proxy: EventLoopProxy<AppEvent>,
+) {
+    let Some(win) = web_sys::window() else {
+        return;
+    };
+
+    wasm_bindgen_futures::spawn_local(async move {
+        let written = async {
+            let handle = match handle {
+                Some(handle) => handle,
+                None => {
+                    let options = js_sys::Object::new();
+                    js_sys::Reflect::set(
+                        &options,
+                        &"suggestedName".into(),
+                        &suggested_name.into(),
+                    )?;
+                    let args = js_sys::Array::of1(&options);
+                    FileHandle(call_async(&win, "showSaveFilePicker", &args).await?)
+                }
+            };
+            let writable = call_async(&handle.0, "createWritable", &js_sys::Array::new()).await?;
+            call_async(&writable, "write", &js_sys::Array::of1(&text.into())).await?;
+            call_async(&writable, "close", &js_sys::Array::new()).await?;
+            Ok::<_, JsValue>(handle)
+        };
+        let event = match written.await {
+            Ok(handle) => AppEvent::FileSaved {
+                handle,
+                saved,
+                auto,
+            },
+            Err(err) if cancelled(&err) => return,
+            Err(err) => AppEvent::Message(format!("Failed to save: {err:?}")),
+        };
+        let _ = proxy.send_event(event);
+    });
+}
+
+/// How far auto-save has got with the latest edits, shown in the status bar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SaveState {
+    /// Nothing has been edited since the page loaded, or there is nowhere to
+    /// save to.
+    #[default]
+    Idle,
+    /// Edits are waiting for the typing to pause, or being written.
+    Saving,
+    Saved,
+}
+
+impl SaveState {
+    pub fn label(self) -> Option<&'static str> {
+        match self {
+            Self::Idle => None,
+            Self::Saving => Some("Saving…"),
+            Self::Saved => Some("Saved"),
+        }
+    }
+}
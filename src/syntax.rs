@@ -0,0 +1,319 @@
+use std::ops::Range;
+
+use ratatui::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    Rust,
+}
+
+impl Language {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "rust" | "rs" => Some(Self::Rust),
+            _ => None,
+        }
+    }
+
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        Self::parse(name.rsplit_once('.')?.1)
+    }
+
+    pub fn highlighter(self) -> &'static dyn Highlighter {
+        match self {
+            Self::Rust => &RustHighlighter,
+        }
+    }
+}
+
+/// Produces the syntax highlighting for a document. Supporting another
+/// language means implementing this and adding a `Language` for it.
+pub trait Highlighter {
+    /// Returns the tokens to style, in order and without overlaps.
+    fn highlight(&self, text: &str) -> Vec<Token>;
+}
+
+/// Leaves the document unstyled.
+pub struct PlainText;
+
+impl Highlighter for PlainText {
+    fn highlight(&self, _text: &str) -> Vec<Token> {
+        vec![]
+    }
+}
+
+pub struct RustHighlighter;
+
+impl Highlighter for RustHighlighter {
+    fn highlight(&self, text: &str) -> Vec<Token> {
+        tokenize_rust(text)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Keyword,
+    String,
+    Comment,
+    Number,
+}
+
+impl TokenKind {
+    pub fn style(self) -> Style {
+        let color = match self {
+            Self::Keyword => Color::Rgb(197, 134, 192),
+            Self::String => Color::Rgb(206, 145, 120),
+            Self::Comment => Color::Rgb(106, 153, 85),
+            Self::Number => Color::Rgb(181, 206, 168),
+        };
+        Style::new().fg(color)
+    }
+}
+
+/// A highlighted run of the document, by byte range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub bytes: Range<usize>,
+    pub kind: TokenKind,
+}
+
+pub const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while",
+];
+
+/// The length in bytes of the quoted literal at the start of `text`, which
+/// must begin with the opening quote. Unterminated literals run to the end.
+pub fn quoted_len(text: &str, quote: char) -> usize {
+    let mut chars = text.char_indices().skip(1);
+    while let Some((idx, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if c == quote {
+            return idx + c.len_utf8();
+        }
+    }
+    text.len()
+}
+
+/// Splits Rust source into the tokens worth highlighting. This is a lexer in
+/// the loosest sense. It only needs to find comments, literals, and keywords,
+/// and anything it doesn't recognize is left unstyled.
+pub fn tokenize_rust(text: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut idx = 0;
+    while let Some(c) = text[idx..].chars().next() {
+        let rest = &text[idx..];
+        let start = idx;
+        let kind = if rest.starts_with("//") {
+            idx += rest.find('\n').unwrap_or(rest.len());
+            Some(TokenKind::Comment)
+        } else if rest.starts_with("/*") {
+            // Block comments nest.
+            let mut depth = 0;
+            let mut end = 0;
+            while end < rest.len() {
+                if rest[end..].starts_with("/*") {
+                    depth += 1;
+                    end += 2;
+                } else if rest[end..].starts_with("*/") {
+                    depth -= 1;
+                    end += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    end += rest[end..].chars().next().map_or(1, char::len_utf8);
+                }
+            }
+            idx += end;
+            Some(TokenKind::Comment)
+        } else if let Some(raw) = rest
+            .strip_prefix("br")
+            .or_else(|| rest.strip_prefix('r'))
+            .filter(|raw| raw.starts_with(['"', '#']))
+        {
+            let hashes = raw.len() - raw.trim_start_matches('#').len();
+            if raw[hashes..].starts_with('"') {
+                let closing = format!("\"{}", "#".repeat(hashes));
+                let body = rest.len() - raw.len() + hashes + 1;
+                idx += rest[body..]
+                    .find(&closing)
+                    .map_or(rest.len(), |end| body + end + closing.len());
+                Some(TokenKind::String)
+            } else {
+                idx += rest.len() - raw.len();
+                // `r#` in front of an identifier makes it a raw identifier, which is
+                // never a keyword even when it's spelled like one.
+                if hashes == 1 && rest.starts_with('r') {
+                    idx += 1 + raw[1..]
+                        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                        .unwrap_or(raw.len() - 1);
+                }
+                None
+            }
+        } else if rest.starts_with('"') || rest.starts_with("b\"") {
+            let prefix = usize::from(rest.starts_with('b'));
+            idx += prefix + quoted_len(&rest[prefix..], '"');
+            Some(TokenKind::String)
+        } else if c == '\'' {
+            // A quote starts either a character literal or a lifetime, which only the
+            // character after it can tell apart.
+            let mut chars = rest.chars().skip(1);
+            match (chars.next(), chars.next()) {
+                (Some('\\'), _) | (Some(_), Some('\'')) => {
+                    idx += quoted_len(rest, '\'');
+                    Some(TokenKind::String)
+                }
+                _ => {
+                    idx += 1;
+                    None
+                }
+            }
+        } else if c.is_ascii_digit() {
+            // Stops before `..` so ranges like `0..10` aren't taken as one number.
+            let mut end = 1;
+            while let Some(c) = rest[end..].chars().next() {
+                let fraction = c == '.' && !rest[end..].starts_with("..");
+                if !(c.is_ascii_alphanumeric() || c == '_' || fraction) {
+                    break;
+                }
+                end += 1;
+            }
+            idx += end;
+            Some(TokenKind::Number)
+        } else if c.is_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            idx += end;
+            RUST_KEYWORDS
+                .contains(&&rest[..end])
+                .then_some(TokenKind::Keyword)
+        } else {
+            idx += c.len_utf8();
+            None
+        };
+
+        if let Some(kind) = kind {
+            tokens.push(Token {
+                bytes: start..idx,
+                kind,
+            });
+        }
+    }
+
+    tokens
+}
+
+pub fn token_style(tokens: &[Token], byte: usize) -> Style {
+    let idx = tokens.partition_point(|token| token.bytes.end <= byte);
+    tokens
+        .get(idx)
+        .filter(|token| token.bytes.contains(&byte))
+        .map_or(Style::default(), |token| token.kind.style())
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::*;
+
+    /// The highlighted text of each token in `text`.
+    fn tokens(text: &str) -> Vec<(&str, TokenKind)> {
+        tokenize_rust(text)
+            .into_iter()
+            .map(|token| (&text[token.bytes], token.kind))
+            .collect()
+    }
+
+    #[wasm_bindgen_test]
+    fn keywords_are_whole_identifiers() {
+        assert_eq!(
+            tokens("pub fn format(self) -> Self { let r#in = 1; }"),
+            [
+                ("pub", TokenKind::Keyword),
+                ("fn", TokenKind::Keyword),
+                ("self", TokenKind::Keyword),
+                ("Self", TokenKind::Keyword),
+                ("let", TokenKind::Keyword),
+                ("1", TokenKind::Number),
+            ]
+        );
+        assert!(tokens("returned break_ é_fn r#match").is_empty());
+    }
+
+    #[wasm_bindgen_test]
+    fn comments_run_to_the_end_of_the_line_or_nest() {
+        assert_eq!(
+            tokens("x // let\nlet"),
+            [("// let", TokenKind::Comment), ("let", TokenKind::Keyword)]
+        );
+        assert_eq!(
+            tokens("/* a /* b */ c */ fn"),
+            [
+                ("/* a /* b */ c */", TokenKind::Comment),
+                ("fn", TokenKind::Keyword)
+            ]
+        );
+        // An unterminated comment takes the rest of the text.
+        assert_eq!(tokens("/* é"), [("/* é", TokenKind::Comment)]);
+    }
+
+    #[wasm_bindgen_test]
+    fn string_literals_end_at_their_closing_quote() {
+        assert_eq!(
+            tokens(r#""a\"b" b"c" 'd' '\n' 'é'"#),
+            [
+                (r#""a\"b""#, TokenKind::String),
+                (r#"b"c""#, TokenKind::String),
+                ("'d'", TokenKind::String),
+                (r"'\n'", TokenKind::String),
+                ("'é'", TokenKind::String),
+            ]
+        );
+        assert_eq!(
+            tokens(r###"r#"say "hi""# r"x""###),
+            [
+                (r###"r#"say "hi""#"###, TokenKind::String),
+                (r#"r"x""#, TokenKind::String),
+            ]
+        );
+        // Lifetimes aren't literals.
+        assert!(tokens("&'a str").is_empty());
+        assert_eq!(tokens("\"open"), [("\"open", TokenKind::String)]);
+    }
+
+    #[wasm_bindgen_test]
+    fn numbers_stop_before_ranges() {
+        assert_eq!(
+            tokens("0..10 1.5e3 0xFF_u8"),
+            [
+                ("0", TokenKind::Number),
+                ("10", TokenKind::Number),
+                ("1.5e3", TokenKind::Number),
+                ("0xFF_u8", TokenKind::Number),
+            ]
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn languages_come_from_the_file_extension() {
+        assert_eq!(Language::from_file_name("main.rs"), Some(Language::Rust));
+        assert_eq!(Language::from_file_name("notes.txt"), None);
+        assert_eq!(Language::from_file_name("Makefile"), None);
+        assert_eq!(Language::parse("rust"), Some(Language::Rust));
+    }
+
+    #[wasm_bindgen_test]
+    fn styles_come_from_the_token_under_a_byte() {
+        let tokens = tokenize_rust("let x");
+        assert_eq!(token_style(&tokens, 0), TokenKind::Keyword.style());
+        assert_eq!(token_style(&tokens, 2), TokenKind::Keyword.style());
+        assert_eq!(token_style(&tokens, 3), Style::default());
+        assert_eq!(token_style(&tokens, 4), Style::default());
+    }
+}